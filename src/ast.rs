@@ -1,10 +1,10 @@
 //! The output AST.
 #![allow(missing_docs)]
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Block(pub Vec<Statement>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Empty,
     Block(Block),
@@ -17,6 +17,7 @@ pub enum Statement {
         is_constructor: bool,
         inherit: Option<Call>,
         params: Vec<Param>,
+        body: Block,
     },
     Var(Vec<(String, Option<Expr>)>),
     Static(Vec<(String, Option<Expr>)>),
@@ -39,17 +40,17 @@ pub enum Statement {
         body: Box<Statement>,
     },
     While(LoopStmt),
+    DoUntil(LoopStmt),
     Repeat(LoopStmt),
     Switch {
         target: Box<Expr>,
         cases: Vec<SwitchCase>,
-        default: Option<Block>,
     },
     With(LoopStmt),
     TryCatch {
         try_block: Box<Statement>,
-        err: String,
-        catch_block: Box<Statement>,
+        catch: Option<(String, Box<Statement>)>,
+        finally_block: Option<Box<Statement>>,
     },
     Throw(Box<Expr>),
     Call(Call),
@@ -59,32 +60,32 @@ pub enum Statement {
     Continue,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Call {
     pub base: Box<Expr>,
     pub arguments: Vec<Expr>,
     pub has_new: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Param {
     pub name: String,
     pub default: Expr,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LoopStmt {
-    target: Box<Expr>,
-    body: Box<Statement>,
+    pub target: Box<Expr>,
+    pub body: Box<Statement>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SwitchCase {
-    pub compare: Expr,
+    pub compare: Option<Expr>,
     pub body: Block,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Mutation {
     pub op: MutationOp,
     pub target: Box<MutableExpr>,
@@ -96,7 +97,7 @@ pub enum MutationOp {
     Decrement,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MutableExpr {
     Ident(String),
     Field {
@@ -133,7 +134,7 @@ pub enum AssignmentOp {
     NullCoalesce,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Global,
     This,
@@ -164,7 +165,7 @@ pub enum Expr {
         base: Box<Expr>,
         field: String,
     },
-    Index{
+    Index {
         base: Box<Expr>,
         accessor_type: Option<AccessorType>,
         indexes: Vec<Expr>,
@@ -175,7 +176,7 @@ pub enum Expr {
     ArgumentCount,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOp {
     Add,
     Sub,
@@ -200,17 +201,17 @@ pub enum BinaryOp {
     NullCoalesce,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp {
     Not,
     Minus,
     BitNegate,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Field {
     Value(String, Expr),
-    Init(String)
+    Init(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -69,7 +69,6 @@ impl<Meta: Clone + Debug> ControlFlowGraph<Meta> {
     /// Insert a node without a parent.
     pub fn insert_parentless(&mut self, this: NodeRef, meta: Meta) {
         if self.nodes.is_empty() {
-            eprintln!("_ -> {this}");
             self.nodes.insert(
                 this,
                 ControlFlowNode {
@@ -82,7 +81,6 @@ impl<Meta: Clone + Debug> ControlFlowGraph<Meta> {
             self.root = Some(this);
             return;
         }
-        eprintln!("_ -> {this}");
         match self.nodes.entry(this) {
             std::collections::hash_map::Entry::Occupied(mut entry) => {
                 entry.get_mut().meta = meta;
@@ -100,7 +98,6 @@ impl<Meta: Clone + Debug> ControlFlowGraph<Meta> {
     /// Push a new node to the control flow graph with the provided parent.
     pub fn insert(&mut self, parent: NodeRef, this: NodeRef, meta: Meta) {
         if self.nodes.is_empty() {
-            eprintln!("_ -> {this}");
             self.nodes.insert(
                 this,
                 ControlFlowNode {
@@ -113,7 +110,6 @@ impl<Meta: Clone + Debug> ControlFlowGraph<Meta> {
             self.root = Some(this);
             return;
         }
-        eprintln!("{parent} -> {this}");
         match self.nodes.entry(this) {
             std::collections::hash_map::Entry::Occupied(mut entry) => {
                 entry.get_mut().parents.insert(parent);
@@ -131,16 +127,40 @@ impl<Meta: Clone + Debug> ControlFlowGraph<Meta> {
 
         self.nodes.get_mut(&parent).unwrap().children.insert(this);
     }
+    /// Add an edge between two nodes that are already in the CFG.
+    pub fn connect(&mut self, parent: NodeRef, child: NodeRef) {
+        self.nodes.get_mut(&parent).unwrap().children.insert(child);
+        self.nodes.get_mut(&child).unwrap().parents.insert(parent);
+    }
     /// Remove the provided item from the CFG.
     pub fn remove(&mut self, this: NodeRef) {
         let node = self.nodes.remove(&this).unwrap();
         for child in node.children {
-            self.nodes.get_mut(&child).unwrap().parents.remove(&this);
+            if child != this {
+                self.nodes.get_mut(&child).unwrap().parents.remove(&this);
+            }
         }
         for parent in node.parents {
-            self.nodes.get_mut(&parent).unwrap().children.remove(&this);
+            if parent != this {
+                self.nodes.get_mut(&parent).unwrap().children.remove(&this);
+            }
+        }
+        if self.root == Some(this) {
+            self.root = None;
         }
     }
+    /// Get the root of this CFG, if it has one.
+    pub fn root(&self) -> Option<NodeRef> {
+        self.root
+    }
+    /// Set the root of this CFG. The node must already be in the CFG.
+    pub fn set_root(&mut self, root: NodeRef) {
+        assert!(
+            self.has(root),
+            "cannot set root to {root}, which is not in the CFG"
+        );
+        self.root = Some(root);
+    }
     /// Get the children of a node.
     pub fn children_of(&self, node: NodeRef) -> &HashSet<NodeRef> {
         &self.nodes[&node].children
@@ -171,10 +191,20 @@ impl<Meta: Clone + Debug> ControlFlowGraph<Meta> {
             keys: self.nodes.keys(),
         }
     }
+    /// Get an iterator over the items in ascending order.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = NodeRef> {
+        let mut nodes = self.nodes.keys().copied().collect::<Vec<_>>();
+        nodes.sort_by_key(|v| v.0);
+        nodes.into_iter()
+    }
     /// Get the number of nodes in this graph.
     pub fn len(&self) -> usize {
         self.nodes.len()
     }
+    /// Whether this graph has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
 }
 
 type Ed = (NodeRef, NodeRef);
@@ -2,6 +2,8 @@
 
 pub mod ast;
 pub mod cfg;
+mod passes;
+mod resolvers;
 
 use std::{
     collections::{HashMap, HashSet, VecDeque},
@@ -11,7 +13,10 @@ use std::{
 use libgm::{
     gml::{
         GMCode, Instruction,
-        instruction::{AssetReference, DataType, PushValue},
+        instruction::{
+            AssetReference, CodeVariable, ComparisonType, DataType, InstanceType, PushValue,
+            VariableType,
+        },
     },
     prelude::*,
 };
@@ -19,6 +24,11 @@ use libgm::{
 use crate::{
     ast::{BinaryOp, Constant},
     cfg::{ControlFlowGraph, NodeRef},
+    resolvers::{
+        DoUntilResolver, IS_NULLISH, IfResolver, JumpResolver, NullCoalesceResolver,
+        RepeatResolver, ShortCircuitResolver, SwitchResolver, TRY_HOOK, TernaryResolver,
+        TryResolver, WhileResolver, WithResolver, is_call_to,
+    },
 };
 
 fn get_index_from_bytes(instructions: &[Instruction], byte_index: u32) -> Result<usize> {
@@ -67,24 +77,20 @@ fn get_index_from_byte_offset(
     }
 }
 
-fn create_instr_cfg_from_code(code: &GMCode) -> Result<ControlFlowGraph<()>> {
+fn create_instr_cfg_from_code(code: &GMCode, data: &GMData) -> Result<ControlFlowGraph<()>> {
     let start_i = get_index_from_bytes(&code.instructions, code.execution_offset())
         .context("decompile_one")?;
     let mut cfg = cfg::ControlFlowGraph::new_rootless();
 
     let mut i_next: VecDeque<(NodeRef, NodeRef)> = vec![(NodeRef(0), NodeRef(start_i))].into();
-    let mut seen_once = HashSet::new();
-    loop {
-        let i = &mut i_next.pop_front();
-        if i.is_none() || *i.unwrap().1 >= code.instructions.len() {
-            break;
+    while let Some((parent, i)) = i_next.pop_front() {
+        if *i >= code.instructions.len() {
+            continue;
         }
-        let (parent, i) = i.unwrap();
 
-        if cfg.has(i) && seen_once.contains(&i) {
+        if cfg.has(i) {
+            cfg.connect(parent, i);
             continue;
-        } else if cfg.has(i) {
-            seen_once.insert(i);
         }
 
         let instr = &code.instructions[*i];
@@ -113,9 +119,14 @@ fn create_instr_cfg_from_code(code: &GMCode) -> Result<ControlFlowGraph<()>> {
             }
             Instruction::Return | Instruction::Exit => {}
             _ => {
+                // nothing jumps to a catch block, so connect it to where the
+                // try statement starts
+                if let Some(catch) = try_hook_catch(code, data, *i)? {
+                    i_next.push_back((i, NodeRef(catch)));
+                }
                 i_next.push_back((i, NodeRef(*i + 1)));
             }
-        };
+        }
 
         cfg.insert(parent, i, ());
     }
@@ -123,6 +134,68 @@ fn create_instr_cfg_from_code(code: &GMCode) -> Result<ControlFlowGraph<()>> {
     Ok(cfg)
 }
 
+/// If instruction `i` is the `popz` after the call to [`TRY_HOOK`] starting a
+/// try statement, get the index of the start of its catch block.
+///
+/// ```text
+/// push.i <finally address>
+/// conv.i.v
+/// push.i <catch address>
+/// conv.i.v
+/// call.i @@try_hook@@(argc=2)
+/// popz.v
+/// ```
+///
+/// The catch address is `-1` if there's no catch block.
+fn try_hook_catch(code: &GMCode, data: &GMData, i: usize) -> Result<Option<usize>> {
+    if !is_try_hook(code, data, i)? {
+        return Ok(None);
+    }
+
+    let address = code.instructions[..i - 1]
+        .iter()
+        .rev()
+        .find(|v| !matches!(v, Instruction::Convert { from: _, to: _ }));
+    let address = match address {
+        Some(Instruction::Push {
+            value: PushValue::Int16(v),
+        }) => i64::from(*v),
+        Some(Instruction::Push {
+            value: PushValue::Int32(v),
+        }) => i64::from(*v),
+        Some(Instruction::Push {
+            value: PushValue::Int64(v),
+        }) => *v,
+        Some(Instruction::PushImmediate { integer }) => i64::from(*integer),
+        _ => bail!("could not find the catch address of the try statement at {i}"),
+    };
+    if address < 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        get_index_from_bytes(&code.instructions, address as u32).context("try_hook_catch")?,
+    ))
+}
+
+/// Whether instruction `i` is the `popz` after the call to [`TRY_HOOK`].
+fn is_try_hook(code: &GMCode, data: &GMData, i: usize) -> Result<bool> {
+    let (
+        Some(Instruction::PopDiscard { data_type: _ }),
+        Some(Instruction::Call {
+            function,
+            argument_count: _,
+        }),
+    ) = (
+        code.instructions.get(i),
+        i.checked_sub(1).and_then(|v| code.instructions.get(v)),
+    )
+    else {
+        return Ok(false);
+    };
+    Ok(function.resolve(&data.functions.functions)?.name == TRY_HOOK)
+}
+
 #[derive(Clone, Debug)]
 struct BlockMeta {
     instr_range: Range<usize>,
@@ -130,104 +203,710 @@ struct BlockMeta {
 }
 
 /// convert a per-instruction CFG into a list of blocks and a block CFG
+///
+/// Blocks are numbered in the order they appear in the code, so a block's
+/// [`NodeRef`] can be compared against another's to tell which comes first.
 fn instr_cfg_to_block_cfg(
     code: &GMCode,
-    in_cfg: ControlFlowGraph<()>,
-) -> ControlFlowGraph<BlockMeta> {
-    let mut leaders = Vec::new();
-    let mut trailers = Vec::new();
-
-    leaders.push(NodeRef(0));
+    data: &GMData,
+    in_cfg: &ControlFlowGraph<()>,
+) -> Result<ControlFlowGraph<BlockMeta>> {
+    let mut out_cfg = ControlFlowGraph::new_rootless();
+    let Some(root) = in_cfg.root() else {
+        return Ok(out_cfg);
+    };
 
+    // a leader is the first instruction of a block: the entry, anything that
+    // can be jumped to, and anything following an instruction that can jump.
+    // the try block is kept apart from its hook too, even when there's no
+    // catch block branching off to split them
+    let mut leaders = HashSet::new();
+    leaders.insert(root);
     for node in in_cfg.iter() {
-        match in_cfg.children_of(node).len() {
-            0 => {
-                trailers.push(node);
-                if *node < in_cfg.len() - 1 {
-                    eprintln!("{node}");
-                    leaders.push(NodeRef(*node + 1));
-                }
-            }
-            1 => {}
-            _ => {
-                trailers.push(node);
-                if *node < in_cfg.len() - 1 {
-                    eprintln!("{node}");
-                    leaders.push(NodeRef(*node + 1));
-                }
-            }
+        let children = in_cfg.children_of(node);
+        if children.len() != 1
+            || matches!(
+                code.instructions[*node],
+                Instruction::Branch { jump_offset: _ }
+            )
+            || is_try_hook(code, data, *node)?
+        {
+            leaders.extend(children.iter().copied());
         }
 
-        if matches!(
-            code.instructions[*node],
-            Instruction::Branch { jump_offset: _ }
-        ) {
-            trailers.push(node);
-            if *node < in_cfg.len() - 1 {
-                eprintln!("{node}");
-                leaders.push(NodeRef(*node + 1));
-            }
+        let parents = in_cfg.parents_of(node);
+        if parents.len() > 1 || parents.iter().any(|parent| **parent + 1 != *node) {
+            leaders.insert(node);
         }
     }
 
-    let blocks = trailers
-        .into_iter()
-        .zip(leaders.into_iter())
+    let mut leaders = leaders.into_iter().collect::<Vec<_>>();
+    leaders.sort_by_key(|v| **v);
+    let block_of = leaders
+        .iter()
         .enumerate()
-        .map(|v| (v.1.0, (v.0, v.1.1)))
+        .map(|(i, start)| (*start, NodeRef(i)))
         .collect::<HashMap<_, _>>();
 
-    let mut out_cfg = ControlFlowGraph::new_rootless();
+    let mut trailers = Vec::with_capacity(leaders.len());
+    for (i, start) in leaders.iter().enumerate() {
+        let mut end = *start;
+        loop {
+            let children = in_cfg.children_of(end);
+            let Some(next) = children.iter().next().copied() else {
+                break;
+            };
+            if children.len() != 1 || *next != *end + 1 || block_of.contains_key(&next) {
+                break;
+            }
+            end = next;
+        }
 
-    for (end, (i, start)) in &blocks {
-        let parents = in_cfg
-            .parents_of(*start)
-            .iter()
-            .copied()
-            .collect::<Vec<_>>(); // would be great to remove this allocation but rust gets mad about aliasing
-        for parent in parents {
-            out_cfg.insert_parentless(
-                NodeRef(blocks[&parent].0),
-                BlockMeta {
-                    instr_range: Range::default(),
-                    resolve_state: ResolveState::Unresolved,
-                },
-            );
-            out_cfg.insert(
-                NodeRef(blocks[&parent].0),
-                NodeRef(*i),
-                BlockMeta {
-                    instr_range: **start..(**end + 1),
-                    resolve_state: ResolveState::Unresolved,
-                },
-            );
+        out_cfg.insert_parentless(
+            NodeRef(i),
+            BlockMeta {
+                instr_range: **start..(*end + 1),
+                resolve_state: ResolveState::Unresolved,
+            },
+        );
+        trailers.push(end);
+    }
+
+    for (i, end) in trailers.into_iter().enumerate() {
+        for child in in_cfg.children_of(end) {
+            out_cfg.connect(NodeRef(i), block_of[child]);
         }
     }
 
-    out_cfg
+    out_cfg.set_root(block_of[&root]);
+
+    Ok(out_cfg)
 }
 
 /// Decompile a single code entry.
 pub fn decompile_one(code: &GMCode, data: &GMData) -> Result<String> {
-    let instr_cfg = create_instr_cfg_from_code(code)?;
+    let block = decompile(code, data)?;
+    Ok(format!("{block:#?}"))
+}
 
-    let cfg = instr_cfg_to_block_cfg(code, instr_cfg);
+/// Decompile a single code entry into the statements making it up.
+pub fn decompile(code: &GMCode, data: &GMData) -> Result<ast::Block> {
+    let instr_cfg = create_instr_cfg_from_code(code, data)?;
 
-    let mut out = String::new();
+    let mut cfg = instr_cfg_to_block_cfg(code, data, &instr_cfg)?;
 
-    for i in 0..cfg.len() {
-        if let Some(res) = StraightLineResolver::try_resolve(&cfg, code, data, NodeRef(i))? {
-            out.push_str(&format!(
-                "\n{}",
-                match res.merged_into {
-                    ResolveState::Resolved(v) => format!("{v:#?}"),
-                    _ => unreachable!(),
+    let mut block = resolve_cfg(&mut cfg, code, data)?;
+    let mut locals = collect_locals(code, data, instr_cfg.iter_sorted())?;
+
+    fold_struct_literals(&mut block, data);
+    passes::fold_accessors(&mut block);
+    passes::fold_compound_assignments(&mut block);
+    let declared = passes::fold_for_loops(&mut block, &locals);
+    let params = parameter_names(code, data);
+    passes::name_arguments(&mut block, &params);
+    let caught = passes::catch_variables(&mut block);
+    locals.retain(|v| !declared.contains(v) && !params.contains(v) && !caught.contains(v));
+    // the bodies of these have already been through the passes above
+    fold_function_declarations(&mut block, code, data)?;
+
+    if !locals.is_empty() {
+        let declaration = ast::Statement::Var(locals.into_iter().map(|v| (v, None)).collect());
+        block.0.insert(0, declaration);
+    }
+
+    Ok(block)
+}
+
+/// The marker for whether the static variables of a function have been
+/// initialized yet, which is checked at the start of it.
+const STATIC_INITIALIZED: &str = "@@static_initialized@@";
+
+/// Turn the statements binding the functions declared in `code` back into
+/// the declarations, with the bodies decompiled from the child code entries.
+///
+/// ```text
+/// foo = method(-1, gml_Script_foo);  =>  function foo(argument0) { ... }
+/// ```
+///
+/// The bodies are placed in between the rest of the code, and jumped over.
+/// Constructors are bound to no instance at all. Functions can be declared in
+/// any block, and the code entries of all of them are children of the root
+/// code entry, even for functions declared inside other functions.
+fn fold_function_declarations(block: &mut ast::Block, code: &GMCode, data: &GMData) -> Result<()> {
+    let mut result = Ok(());
+    passes::visit_blocks(block, &mut |block| {
+        if result.is_ok() {
+            result = fold_function_declarations_in(block, code, data);
+        }
+    });
+    result
+}
+
+/// Turn the statements binding functions directly in `block` back into the
+/// declarations, as [`fold_function_declarations`] does.
+fn fold_function_declarations_in(
+    block: &mut ast::Block,
+    code: &GMCode,
+    data: &GMData,
+) -> Result<()> {
+    let mut i = 0;
+    while i < block.0.len() {
+        let ast::Statement::Assignment {
+            target,
+            op: ast::AssignmentOp::Equal,
+            value,
+        } = &block.0[i]
+        else {
+            i += 1;
+            continue;
+        };
+        let (ast::MutableExpr::Ident(name) | ast::MutableExpr::Field { field: name, .. }) = target
+        else {
+            i += 1;
+            continue;
+        };
+        let ast::Expr::Call(ast::Call {
+            base,
+            arguments,
+            has_new: false,
+        }) = value.as_ref()
+        else {
+            i += 1;
+            continue;
+        };
+        let (ast::Expr::Ident(method), [instance, ast::Expr::Ident(function)]) =
+            (base.as_ref(), arguments.as_slice())
+        else {
+            i += 1;
+            continue;
+        };
+        // a declaration binds the function to `self`, or to nothing at all for
+        // constructors, under its own name. anything else is a method made
+        // out of a function declared somewhere else
+        let is_constructor = matches!(
+            instance,
+            ast::Expr::Call(ast::Call { base, .. })
+                if matches!(base.as_ref(), ast::Expr::Ident(v) if v == "@@NullObject@@")
+        );
+        let is_self = matches!(
+            instance,
+            ast::Expr::This | ast::Expr::Constant(Constant::Integer(-1))
+        );
+        if method != "method"
+            || !(is_self || is_constructor)
+            || function.strip_prefix("gml_Script_").unwrap_or(function) != name
+        {
+            i += 1;
+            continue;
+        }
+        let Some(child) = child_function_code(code, data, function)? else {
+            i += 1;
+            continue;
+        };
+
+        // the constructors of struct literals are only bound for the literals
+        // to call, and those are folded back on their own
+        let declaration = if function.contains(STRUCT_PREFIX) {
+            None
+        } else {
+            Some(function_declaration(
+                name.clone(),
+                is_constructor,
+                parameter_names(child, data),
+                decompile(child, data)?,
+            ))
+        };
+
+        // the bound method is duplicated to be assigned, and the copy left
+        // behind is thrown away
+        let value = value.clone();
+        let next = if let Some(declaration) = declaration {
+            block.0[i] = declaration;
+            i + 1
+        } else {
+            block.0.remove(i);
+            i
+        };
+        if let Some(ast::Statement::Call(call)) = block.0.get(next)
+            && ast::Expr::Call(call.clone()) == *value
+        {
+            block.0.remove(next);
+        }
+        i = next;
+    }
+    Ok(())
+}
+
+/// Get the code entry `code` belongs to, whose instructions it shares. This is
+/// `code` itself unless it is the code entry of a function.
+fn root_code<'a>(code: &'a GMCode, data: &'a GMData) -> Result<&'a GMCode> {
+    match code.modern_data.as_ref().and_then(|v| v.parent.as_ref()) {
+        Some(parent) => Ok(parent.resolve(&data.codes.codes)?),
+        None => Ok(code),
+    }
+}
+
+/// Get the code entries of the functions declared anywhere in the code entry
+/// `code` belongs to, which are all children of its root code entry.
+fn function_codes<'a>(code: &GMCode, data: &'a GMData) -> Result<Vec<&'a GMCode>> {
+    let root = &root_code(code, data)?.name;
+    let mut children = Vec::new();
+    for child in &data.codes.codes {
+        let Some(parent) = child.modern_data.as_ref().and_then(|v| v.parent.as_ref()) else {
+            continue;
+        };
+        if parent.resolve(&data.codes.codes)?.name == *root {
+            children.push(child);
+        }
+    }
+    Ok(children)
+}
+
+/// Whether the instructions from `start` up to `end` are the body of a function
+/// declared in `code`, which the branch right before them jumps over.
+///
+/// ```text
+///       b over
+/// body: ...
+///       exit.i
+/// over: push.i gml_Script_foo
+/// ```
+fn is_function_body(code: &GMCode, data: &GMData, start: usize, end: usize) -> Result<bool> {
+    if start >= end
+        || !matches!(
+            start.checked_sub(1).and_then(|v| code.instructions.get(v)),
+            Some(Instruction::Branch { jump_offset: _ })
+        )
+    {
+        return Ok(false);
+    }
+    for function in function_codes(code, data)? {
+        if get_index_from_bytes(&code.instructions, function.execution_offset())? == start {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Find the code entry holding the body of `function`, declared in `code`.
+fn child_function_code<'a>(
+    code: &GMCode,
+    data: &'a GMData,
+    function: &str,
+) -> Result<Option<&'a GMCode>> {
+    code_named_after(function_codes(code, data)?, function)
+}
+
+/// Pick the code entry of the function `name` out of `codes`, which is named
+/// after it.
+///
+/// Functions declared outside of scripts also get the name of where they're
+/// declared after theirs, as in `gml_Script_foo_gml_Object_obj_Create_0`.
+/// The code entry of a function `foo_bar` starts the same way as that of `foo`,
+/// so this is only relied on when nothing is named after the function exactly,
+/// and only if one code entry matches.
+fn code_named_after<'a>(
+    codes: impl IntoIterator<Item = &'a GMCode>,
+    name: &str,
+) -> Result<Option<&'a GMCode>> {
+    let script_name = format!("gml_Script_{name}");
+    let prefix = format!("{script_name}_");
+    let mut prefixed = Vec::new();
+    for code in codes {
+        if code.name == name || code.name == script_name {
+            return Ok(Some(code));
+        }
+        if code.name.starts_with(&prefix) {
+            prefixed.push(code);
+        }
+    }
+    match prefixed.as_slice() {
+        [] => Ok(None),
+        [code] => Ok(Some(*code)),
+        codes => bail!(
+            "could not tell which code entry is the function {name} out of {}",
+            codes
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Write the reference to a function `function` the way it is in the source,
+/// without the `gml_Script_` prefix the functions of scripts are named with.
+fn source_function_ref(function: ast::Expr) -> ast::Expr {
+    match function {
+        ast::Expr::Ident(name) => match name.strip_prefix("gml_Script_") {
+            Some(name) => ast::Expr::Ident(name.to_owned()),
+            None => ast::Expr::Ident(name),
+        },
+        function => function,
+    }
+}
+
+/// Get the names of the parameters of the function `code` is the body of.
+///
+/// These come from the locals of the code entry, which list the parameters
+/// first, after the `arguments` array every function has. Any parameter
+/// without a name keeps the `argument0`, `argument1`, ... it's compiled to.
+fn parameter_names(code: &GMCode, data: &GMData) -> Vec<String> {
+    let count = code
+        .modern_data
+        .as_ref()
+        .map_or(0, |v| usize::from(v.arguments_count));
+    let mut variables = data
+        .code_locals
+        .code_locals
+        .iter()
+        .find(|v| v.name == code.name)
+        .map(|v| v.variables.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    variables.sort_by_key(|v| v.index);
+
+    let mut names = variables
+        .into_iter()
+        .filter(|v| v.name != "arguments")
+        .take(count)
+        .map(|v| v.name.clone())
+        .collect::<Vec<_>>();
+    names.extend((names.len()..count).map(|i| format!("argument{i}")));
+    names
+}
+
+/// Build the declaration of a function out of its decompiled body, taking the
+/// parts of the declaration that are compiled into the body back out.
+///
+/// ```text
+/// if (b == undefined)                 function foo(a, b = 3)
+///     b = 3;                              constructor : Parent(a)
+/// Parent(a);                     =>   {
+/// @@CopyStatic@@(Parent);                 static count = 0;
+/// if (!@@static_initialized@@)            ...
+///     count = 0;                      }
+/// ...
+/// ```
+fn function_declaration(
+    name: String,
+    is_constructor: bool,
+    params: Vec<String>,
+    mut body: ast::Block,
+) -> ast::Statement {
+    let mut params = params
+        .into_iter()
+        .map(|name| ast::Param {
+            name,
+            default: ast::Expr::Constant(Constant::Undefined),
+        })
+        .collect::<Vec<_>>();
+    let mut inherit = None;
+
+    let mut i = 0;
+    while i < body.0.len() {
+        // the default value of a parameter
+        if let ast::Statement::If {
+            cond,
+            then,
+            r#else: None,
+        } = &body.0[i]
+            && let ast::Expr::Binary {
+                lhs,
+                op: BinaryOp::Equal,
+                rhs,
+            } = cond.as_ref()
+            && let ast::Expr::Ident(param_name) = lhs.as_ref()
+            && (**rhs == ast::Expr::Constant(Constant::Undefined)
+                || **rhs == ast::Expr::Ident("undefined".to_owned()))
+            && let ast::Statement::Block(then) = then.as_ref()
+            && let [
+                ast::Statement::Assignment {
+                    target: ast::MutableExpr::Ident(target),
+                    op: ast::AssignmentOp::Equal,
+                    value,
+                },
+            ] = then.0.as_slice()
+            && target == param_name
+            && let Some(param) = params.iter_mut().find(|v| v.name == *param_name)
+        {
+            param.default = (**value).clone();
+            body.0.remove(i);
+            continue;
+        }
+
+        // the call to the parent constructor, followed by copying its
+        // statics
+        if is_constructor
+            && inherit.is_none()
+            && let Some(ast::Statement::Call(copy)) = body.0.get(i + 1)
+            && *copy.base == ast::Expr::Ident("@@CopyStatic@@".to_owned())
+            && let ast::Statement::Call(parent) = &body.0[i]
+        {
+            let mut parent = parent.clone();
+            parent.base = Box::new(source_function_ref(*parent.base));
+            inherit = Some(parent);
+            body.0.drain(i..=i + 1);
+            continue;
+        }
+
+        // static variables are only assigned the first time around
+        if let ast::Statement::If {
+            cond,
+            then,
+            r#else: None,
+        } = &body.0[i]
+            && let ast::Expr::Unary {
+                op: ast::UnaryOp::Not,
+                target,
+            } = cond.as_ref()
+            && **target == ast::Expr::Ident(STATIC_INITIALIZED.to_owned())
+            && let ast::Statement::Block(then) = then.as_ref()
+            && let Some(statics) = then
+                .0
+                .iter()
+                .map(|v| match v {
+                    ast::Statement::Assignment {
+                        target: ast::MutableExpr::Ident(name),
+                        op: ast::AssignmentOp::Equal,
+                        value,
+                    } => Some((name.clone(), Some((**value).clone()))),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+        {
+            body.0[i] = ast::Statement::Static(statics);
+        }
+
+        i += 1;
+    }
+
+    ast::Statement::Function {
+        name,
+        is_constructor,
+        inherit,
+        params,
+        body,
+    }
+}
+
+/// The prefix of the names of the constructors GameMaker generates for struct
+/// literals.
+const STRUCT_PREFIX: &str = "___struct___";
+
+/// Turn calls to the constructors GameMaker generates for struct literals back
+/// into the literals.
+///
+/// ```text
+/// new ___struct___3(x + 1, b)  =>  { a: x + 1, b }
+/// ```
+///
+/// The values of the fields are passed as arguments, and the constructor
+/// assigns each one to its field. Anything not written like that is left as a
+/// call to the constructor.
+fn fold_struct_literals(block: &mut ast::Block, data: &GMData) {
+    for statement in &mut block.0 {
+        passes::visit_statement_exprs(statement, &mut |expr| {
+            if let ast::Expr::Call(call) = expr
+                && let Some(fields) = struct_literal(call, data)
+            {
+                *expr = ast::Expr::Object(fields);
+            }
+        });
+    }
+}
+
+/// Get the fields of the struct literal `call` creates, if it is a call to a
+/// struct literal constructor.
+fn struct_literal(call: &ast::Call, data: &GMData) -> Option<Vec<ast::Field>> {
+    let ast::Expr::Ident(name) = call.base.as_ref() else {
+        return None;
+    };
+    if !call.has_new || !name.contains(STRUCT_PREFIX) {
+        return None;
+    }
+    let constructor = code_named_after(&data.codes.codes, name).ok()??;
+    let body = decompile(constructor, data).ok()?;
+    let params = parameter_names(constructor, data);
+
+    let mut fields = Vec::new();
+    for statement in body.0 {
+        let ast::Statement::Assignment {
+            target: ast::MutableExpr::Ident(field),
+            op: ast::AssignmentOp::Equal,
+            value,
+        } = statement
+        else {
+            // the constructor's locals, if it has any, and the end of it
+            if matches!(
+                statement,
+                ast::Statement::Var(_) | ast::Statement::Return(None)
+            ) {
+                continue;
+            }
+            return None;
+        };
+        let value = match argument_index(&value, &params) {
+            Some(i) => call.arguments.get(i)?.clone(),
+            None => *value,
+        };
+        if value == ast::Expr::Ident(field.clone()) {
+            fields.push(ast::Field::Init(field));
+        } else {
+            fields.push(ast::Field::Value(field, value));
+        }
+    }
+    Some(fields)
+}
+
+/// If `expr` reads an argument of the function it's in, whose parameters are
+/// `params`, get which one.
+fn argument_index(expr: &ast::Expr, params: &[String]) -> Option<usize> {
+    match expr {
+        ast::Expr::Ident(name) => params
+            .iter()
+            .position(|v| v == name)
+            .or_else(|| name.strip_prefix("argument")?.parse().ok()),
+        ast::Expr::Index { base, indexes, .. } => match (base.as_ref(), indexes.as_slice()) {
+            (ast::Expr::Ident(name), [ast::Expr::Constant(Constant::Integer(i))])
+                if name == "argument" =>
+            {
+                usize::try_from(*i).ok()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Get the names of the local variables used by the instructions at
+/// `indexes`, in the order they first appear.
+///
+/// Only the instructions that run as part of `code` are looked at, since the
+/// bodies of the functions declared in it are in between.
+fn collect_locals(
+    code: &GMCode,
+    data: &GMData,
+    indexes: impl Iterator<Item = NodeRef>,
+) -> Result<Vec<String>> {
+    let mut locals: Vec<String> = Vec::new();
+    for i in indexes {
+        let variable = match &code.instructions[*i] {
+            Instruction::Push {
+                value: PushValue::Variable(variable),
+            }
+            | Instruction::PushLocal { variable }
+            | Instruction::Pop {
+                variable,
+                type1: _,
+                type2: _,
+            } => variable,
+            _ => continue,
+        };
+        if !matches!(variable.instance_type, InstanceType::Local) {
+            continue;
+        }
+        let name = &variable.variable.resolve(&data.variables.variables)?.name;
+        if !locals.contains(name) {
+            locals.push(name.clone());
+        }
+    }
+    Ok(locals)
+}
+
+/// Collapse a block CFG down into a single block of code.
+///
+/// Every iteration runs the resolvers from most to least specific over every
+/// node (in code order) and applies the first [`Resolution`] found. This
+/// repeats until only one resolved node with nothing after it is left.
+fn resolve_cfg(
+    cfg: &mut ControlFlowGraph<BlockMeta>,
+    code: &GMCode,
+    data: &GMData,
+) -> Result<ast::Block> {
+    let resolvers = resolvers();
+
+    'outer: loop {
+        if cfg.is_empty() {
+            return Ok(ast::Block(Vec::new()));
+        }
+
+        if let Some(root) = cfg.root()
+            && cfg.len() == 1
+            && cfg.children_of(root).is_empty()
+            && let ResolveState::Resolved(block) = &cfg.meta_of(root).resolve_state
+        {
+            return Ok(block.body.clone());
+        }
+
+        let nodes = cfg.iter_sorted().collect::<Vec<_>>();
+        for (_, resolver) in &resolvers {
+            for node in &nodes {
+                if let Some(resolution) = resolver(cfg, code, data, *node)? {
+                    apply_resolution(cfg, resolution);
+                    continue 'outer;
                 }
-            ));
+            }
         }
+
+        bail!(
+            "could not resolve the control flow of {}: stuck with {} blocks",
+            code.name,
+            cfg.len()
+        );
+    }
+}
+
+/// Apply a [`Resolution`] to a block CFG.
+///
+/// The merged node reuses the lowest [`NodeRef`] of the nodes it replaces so
+/// blocks stay in code order, and covers every instruction they covered.
+fn apply_resolution(cfg: &mut ControlFlowGraph<BlockMeta>, resolution: Resolution) {
+    let Resolution {
+        nodes,
+        merged_into,
+        merged_children,
+        merged_parents,
+    } = resolution;
+
+    let Some(merged) = nodes.iter().copied().min_by_key(|v| **v) else {
+        return;
+    };
+
+    let start = nodes
+        .iter()
+        .map(|v| cfg.meta_of(*v).instr_range.start)
+        .min()
+        .unwrap_or_default();
+    let end = nodes
+        .iter()
+        .map(|v| cfg.meta_of(*v).instr_range.end)
+        .max()
+        .unwrap_or_default();
+    let was_root = nodes.iter().any(|v| cfg.root() == Some(*v));
+
+    for node in &nodes {
+        cfg.remove(*node);
+    }
+
+    cfg.insert_parentless(
+        merged,
+        BlockMeta {
+            instr_range: start..end,
+            resolve_state: merged_into,
+        },
+    );
+
+    let map = |v: NodeRef| if nodes.contains(&v) { merged } else { v };
+    for parent in merged_parents {
+        cfg.connect(map(parent), merged);
+    }
+    for child in merged_children {
+        cfg.connect(merged, map(child));
     }
 
-    Ok(out)
+    if was_root {
+        cfg.set_root(merged);
+    }
 }
 
 fn get_code_of_block<'a>(block: &BlockMeta, code: &'a GMCode) -> &'a [Instruction] {
@@ -237,7 +916,111 @@ fn get_code_of_block<'a>(block: &BlockMeta, code: &'a GMCode) -> &'a [Instructio
 #[derive(Clone, Debug)]
 enum ResolveState {
     Unresolved,
-    Resolved(ast::Block),
+    Resolved(ResolvedBlock),
+}
+
+/// The result of resolving one or more blocks.
+#[derive(Clone, Debug)]
+struct ResolvedBlock {
+    /// The statements making up the block.
+    body: ast::Block,
+    /// Values left on the stack when the block finishes, bottom first. These
+    /// are consumed by whatever runs after the block.
+    ///
+    /// This is the block's stack effect. Blocks that take values from before
+    /// them can't be simulated alone and stay unresolved until a resolver
+    /// provides the incoming stack, so the count is never negative.
+    stack: Vec<ast::Expr>,
+    /// The condition tested by the conditional branch ending this block, if
+    /// it ends with one.
+    branch: Option<BranchCondition>,
+    /// Whether the last statement of this block is the step of a `for` loop,
+    /// which `continue` jumps to instead of the top of the loop.
+    for_step: bool,
+}
+
+impl ResolvedBlock {
+    /// A block made of just `body`, with no stack values left and no trailing
+    /// branch.
+    fn from_statements(body: Vec<ast::Statement>) -> Self {
+        Self {
+            body: ast::Block(body),
+            stack: Vec::new(),
+            branch: None,
+            for_step: false,
+        }
+    }
+}
+
+/// The value tested by a `bt` or `bf` instruction.
+#[derive(Clone, Debug)]
+struct BranchCondition {
+    /// The tested value.
+    value: ast::Expr,
+    /// Whether the branch is taken when `value` is true (`bt`) instead of when
+    /// it is false (`bf`).
+    jump_if: bool,
+}
+
+impl BranchCondition {
+    /// The condition under which the branch is taken.
+    fn jump_cond(&self) -> ast::Expr {
+        if self.jump_if {
+            self.value.clone()
+        } else {
+            negate(self.value.clone())
+        }
+    }
+
+    /// The condition under which execution falls through to the next block.
+    fn fallthrough_cond(&self) -> ast::Expr {
+        if self.jump_if {
+            negate(self.value.clone())
+        } else {
+            self.value.clone()
+        }
+    }
+}
+
+/// Logically negate an expression, removing a `!` instead of adding another
+/// one where possible.
+fn negate(expr: ast::Expr) -> ast::Expr {
+    match expr {
+        ast::Expr::Unary {
+            op: ast::UnaryOp::Not,
+            target,
+        } => *target,
+        expr => ast::Expr::Unary {
+            op: ast::UnaryOp::Not,
+            target: Box::new(expr),
+        },
+    }
+}
+
+/// Split the children of a block ending in a conditional branch into the one
+/// reached by falling through and the one reached by jumping, in that order.
+fn split_branch(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    node: NodeRef,
+) -> Option<(NodeRef, NodeRef)> {
+    let children = block_cfg.children_of(node);
+    if children.len() != 2 {
+        return None;
+    }
+    let end = block_cfg.meta_of(node).instr_range.end;
+    let fallthrough = children
+        .iter()
+        .copied()
+        .find(|v| block_cfg.meta_of(*v).instr_range.start == end)?;
+    let target = children.iter().copied().find(|v| *v != fallthrough)?;
+    Some((fallthrough, target))
+}
+
+/// Find the block starting at the instruction `start`.
+fn block_at(block_cfg: &ControlFlowGraph<BlockMeta>, start: usize) -> Option<NodeRef> {
+    block_cfg
+        .iter()
+        .find(|v| block_cfg.meta_of(*v).instr_range.start == start)
 }
 
 /// Removes all of the nodes listed in `nodes` and creates a node emcompassing
@@ -270,6 +1053,376 @@ trait Resolver {
     ) -> Result<Option<Resolution>>;
 }
 
+/// A type-erased [`Resolver::try_resolve`].
+type ResolveFn =
+    fn(&ControlFlowGraph<BlockMeta>, &GMCode, &GMData, NodeRef) -> Result<Option<Resolution>>;
+
+/// Every resolver along with its [`Resolver::SPECIFICITY`], most specific
+/// first.
+fn resolvers() -> Vec<(i16, ResolveFn)> {
+    let mut resolvers: Vec<(i16, ResolveFn)> = vec![
+        (
+            StraightLineResolver::SPECIFICITY,
+            StraightLineResolver::try_resolve,
+        ),
+        (SequenceResolver::SPECIFICITY, SequenceResolver::try_resolve),
+        (IfResolver::SPECIFICITY, IfResolver::try_resolve),
+        (WhileResolver::SPECIFICITY, WhileResolver::try_resolve),
+        (DoUntilResolver::SPECIFICITY, DoUntilResolver::try_resolve),
+        (RepeatResolver::SPECIFICITY, RepeatResolver::try_resolve),
+        (WithResolver::SPECIFICITY, WithResolver::try_resolve),
+        (SwitchResolver::SPECIFICITY, SwitchResolver::try_resolve),
+        (
+            ShortCircuitResolver::SPECIFICITY,
+            ShortCircuitResolver::try_resolve,
+        ),
+        (JumpResolver::SPECIFICITY, JumpResolver::try_resolve),
+        (TryResolver::SPECIFICITY, TryResolver::try_resolve),
+        (TernaryResolver::SPECIFICITY, TernaryResolver::try_resolve),
+        (
+            NullCoalesceResolver::SPECIFICITY,
+            NullCoalesceResolver::try_resolve,
+        ),
+    ];
+    resolvers.sort_by_key(|v| std::cmp::Reverse(v.0));
+    resolvers
+}
+
+/// Resolves two nodes where the first always continues into the second, and
+/// the second can only be reached from the first.
+///
+/// The second node also has to come right after the first in the code, so a
+/// jump to some far away block (a `break`, say) isn't merged in and left
+/// covering all the code it jumped over. The only code that can be jumped over
+/// is the body of a function declared in between, which is decompiled from its
+/// own code entry.
+struct SequenceResolver;
+
+impl Resolver for SequenceResolver {
+    const SPECIFICITY: i16 = i16::MIN + 1;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(first) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        // the try block is kept apart from its hook so the try resolver can
+        // find it, even when there's no catch block branching off
+        if first.branch.is_some() || first.body.0.last().is_some_and(|v| is_call_to(v, TRY_HOOK)) {
+            return Ok(None);
+        }
+
+        let children = block_cfg.children_of(entry);
+        let Some(next) = children.iter().next().copied() else {
+            return Ok(None);
+        };
+        if children.len() != 1
+            || next == entry
+            || block_cfg.root() == Some(next)
+            || block_cfg.parents_of(next).len() != 1
+        {
+            return Ok(None);
+        }
+        // anything between the two blocks has to be a function body jumped over
+        let body_start = block_cfg.meta_of(entry).instr_range.end;
+        let body_end = block_cfg.meta_of(next).instr_range.start;
+        if body_start != body_end && !is_function_body(code, data, body_start, body_end)? {
+            return Ok(None);
+        }
+
+        let Some(second) =
+            resolve_with_stack(block_cfg.meta_of(next), code, data, first.stack.clone())?
+        else {
+            return Ok(None);
+        };
+
+        let mut body = first.body.0.clone();
+        body.extend(second.body.0);
+
+        Ok(Some(Resolution {
+            nodes: [entry, next].into_iter().collect(),
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: ast::Block(body),
+                stack: second.stack,
+                branch: second.branch,
+                for_step: second.for_step,
+            }),
+            merged_children: block_cfg.children_of(next).clone(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Get the resolved form of the block `meta` when `stack` is left on the stack
+/// before it runs.
+///
+/// Unresolved blocks are simulated again, since they usually need those values.
+fn resolve_with_stack(
+    meta: &BlockMeta,
+    code: &GMCode,
+    data: &GMData,
+    mut stack: Vec<ast::Expr>,
+) -> Result<Option<ResolvedBlock>> {
+    match &meta.resolve_state {
+        ResolveState::Resolved(block) => {
+            let mut block = block.clone();
+            stack.append(&mut block.stack);
+            block.stack = stack;
+            Ok(Some(block))
+        }
+        ResolveState::Unresolved => {
+            resolve_straight_line(get_code_of_block(meta, code), data, stack)
+        }
+    }
+}
+
+/// The first ID given to instances placed in rooms. Smaller instances are the
+/// indexes of objects, standing for every instance of them.
+const FIRST_INSTANCE_ID: i64 = 100_000;
+
+/// Turn an instance into the keyword it stands for, if it is one of the
+/// special instance constants, or into the name of the object it is the index
+/// of.
+///
+/// ```text
+/// pushi.e 5
+/// push.v [stacktop]self.hp  =>  obj_player.hp
+/// ```
+fn instance_expr(instance: &ast::Expr, data: &GMData) -> Result<ast::Expr> {
+    Ok(match instance {
+        ast::Expr::Constant(Constant::Integer(-1)) => ast::Expr::This,
+        ast::Expr::Constant(Constant::Integer(-2)) => ast::Expr::Other,
+        ast::Expr::Constant(Constant::Integer(-3)) => ast::Expr::Ident("all".to_string()),
+        ast::Expr::Constant(Constant::Integer(-4)) => ast::Expr::Ident("noone".to_string()),
+        ast::Expr::Constant(Constant::Integer(-5)) => ast::Expr::Global,
+        ast::Expr::Constant(Constant::Integer(object @ 0..FIRST_INSTANCE_ID)) => ast::Expr::Ident(
+            GMRef::new(*object as u32)
+                .resolve(&data.game_objects.game_objects)?
+                .name
+                .clone(),
+        ),
+        instance => instance.clone(),
+    })
+}
+
+/// Build the access to `variable`, taking the instance it belongs to and the
+/// array index off of `stack` when they come from there. Returns `None` if the
+/// stack runs out.
+///
+/// Variables of `self` don't need to be qualified, so they're left as plain
+/// identifiers unless the instance was given explicitly.
+///
+/// ```text
+/// pushi.e -1          pushi.e -1
+/// push.v local.i      push.v local.i
+/// push.v [array]a     push.v [multipush]a
+///                     push.v local.j
+///                     pushaf.e
+/// ```
+///
+/// These are `a[i]` and `a[i][j]`. The `multipush` access pushes `a[i]` for
+/// the array instructions to index into further.
+fn variable_access(
+    variable: &CodeVariable,
+    data: &GMData,
+    stack: &mut Vec<ast::Expr>,
+) -> Result<Option<ast::MutableExpr>> {
+    let name = variable
+        .variable
+        .resolve(&data.variables.variables)?
+        .name
+        .clone();
+
+    let base = match (&variable.variable_type, &variable.instance_type) {
+        (VariableType::Array | VariableType::ArrayPushAF | VariableType::ArrayPopAF, _) => {
+            let (Some(index), Some(instance)) = (stack.pop(), stack.pop()) else {
+                return Ok(None);
+            };
+            // the instance is always pushed for arrays, even when it's implied
+            let base = match instance_expr(&instance, data)? {
+                ast::Expr::This | ast::Expr::Constant(Constant::Integer(-7)) => {
+                    ast::Expr::Ident(name)
+                }
+                base => ast::Expr::Field {
+                    base: Box::new(base),
+                    field: name,
+                },
+            };
+            return Ok(Some(ast::MutableExpr::Index {
+                base: Box::new(base),
+                accessor_type: None,
+                indexes: array_indexes(index),
+            }));
+        }
+        (VariableType::StackTop, _) => {
+            let Some(instance) = stack.pop() else {
+                return Ok(None);
+            };
+            Some(instance_expr(&instance, data)?)
+        }
+        (_, InstanceType::Global) => Some(ast::Expr::Global),
+        (_, InstanceType::Other) => Some(ast::Expr::Other),
+        (_, InstanceType::Self_(Some(object))) => Some(ast::Expr::Ident(
+            object
+                .resolve(&data.game_objects.game_objects)?
+                .name
+                .clone(),
+        )),
+        _ => None,
+    };
+
+    Ok(Some(match base {
+        Some(base) => ast::MutableExpr::Field {
+            base: Box::new(base),
+            field: name,
+        },
+        None => ast::MutableExpr::Ident(name),
+    }))
+}
+
+/// Take the `count` arguments of a call off of `stack`, in the order they're
+/// written in. Returns `None` if there aren't enough values on the stack.
+///
+/// The compiler pushes the arguments from last to first, both in bytecode 14
+/// and before and in bytecode 15 and later, so the stack holds them in the
+/// reverse of source order and the first argument is on top when the call
+/// happens. Popping them one at a time gives them back in source order.
+/// Functions taking a variable number of arguments are called the same way,
+/// with the count of the arguments actually given.
+///
+/// ```text
+/// push.v self.b
+/// push.v self.a
+/// call.i foo(argc=2)   =>  foo(a, b)
+/// ```
+fn pop_arguments(stack: &mut Vec<ast::Expr>, count: usize) -> Option<Vec<ast::Expr>> {
+    let start = stack.len().checked_sub(count)?;
+    let mut arguments = stack.split_off(start);
+    arguments.reverse();
+    Some(arguments)
+}
+
+/// Split the index of an array access into the indexes of a 2D array, which
+/// are flattened into one by GameMaker versions before 2.3.
+///
+/// ```text
+/// a[i, j]  =>  a[i * 32000 + j]
+/// ```
+fn array_indexes(index: ast::Expr) -> Vec<ast::Expr> {
+    if let ast::Expr::Binary {
+        lhs,
+        op: BinaryOp::Add,
+        rhs,
+    } = &index
+        && let ast::Expr::Binary {
+            lhs: first,
+            op: BinaryOp::Mult,
+            rhs: stride,
+        } = lhs.as_ref()
+        && **stride == ast::Expr::Constant(Constant::Integer(32000))
+    {
+        return vec![(**first).clone(), (**rhs).clone()];
+    }
+    vec![index]
+}
+
+/// How an increment or decrement was written.
+enum MutationForm {
+    /// On its own, as `x++;` or `++x;`, which are the same.
+    Statement,
+    /// As `++x` in an expression.
+    Prefix,
+    /// As `x++` in an expression.
+    Postfix,
+}
+
+/// Recognize the `pop` right after `code` storing `value` into `target` as an
+/// increment or decrement.
+///
+/// ```text
+/// statement   prefix      postfix
+/// push.v x    push.v x    push.v x
+///                         dup.v 0
+/// push.e 1    push.e 1    push.e 1
+/// add.i.v     add.i.v     add.i.v
+///             dup.v 0
+/// pop.v.v x   pop.v.v x   pop.v.v x
+/// ```
+///
+/// `x += 1` pushes the 1 with `pushi.e` instead, so it isn't mistaken for one.
+fn find_mutation(
+    code: &[Instruction],
+    target: &ast::MutableExpr,
+    value: &ast::Expr,
+) -> Option<(ast::MutationOp, MutationForm)> {
+    let ast::Expr::Binary { lhs, op, rhs } = value else {
+        return None;
+    };
+    let op = match op {
+        BinaryOp::Add => ast::MutationOp::Increment,
+        BinaryOp::Sub => ast::MutationOp::Decrement,
+        _ => return None,
+    };
+    if **rhs != ast::Expr::Constant(Constant::Integer(1))
+        || **lhs != passes::mutable_as_expr(target)
+    {
+        return None;
+    }
+
+    let form = match code {
+        [
+            ..,
+            Instruction::Duplicate {
+                data_type: _,
+                size: 0,
+            },
+            one,
+            add_sub,
+        ] if is_one(one) && is_add_sub(add_sub) => MutationForm::Postfix,
+        [
+            ..,
+            one,
+            add_sub,
+            Instruction::Duplicate {
+                data_type: _,
+                size: 0,
+            },
+        ] if is_one(one) && is_add_sub(add_sub) => MutationForm::Prefix,
+        [.., one, add_sub] if is_one(one) && is_add_sub(add_sub) => MutationForm::Statement,
+        _ => return None,
+    };
+    Some((op, form))
+}
+
+/// Whether `instr` pushes the 1 of an increment or decrement.
+fn is_one(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Push {
+            value: PushValue::Int16(1)
+        }
+    )
+}
+
+/// Whether `instr` adds or subtracts.
+fn is_add_sub(instr: &Instruction) -> bool {
+    matches!(
+        instr,
+        Instruction::Add {
+            augend: _,
+            addend: _,
+        } | Instruction::Subtract {
+            minuend: _,
+            subtrahend: _,
+        }
+    )
+}
+
+/// Resolves a single block that has no incoming stack values.
 struct StraightLineResolver;
 
 impl Resolver for StraightLineResolver {
@@ -281,267 +1434,514 @@ impl Resolver for StraightLineResolver {
         data: &GMData,
         entry: NodeRef,
     ) -> Result<Option<Resolution>> {
-        let range = block_cfg.meta_of(entry).instr_range.clone();
-        if range.len() <= 1 {
+        let meta = block_cfg.meta_of(entry);
+        if !matches!(meta.resolve_state, ResolveState::Unresolved) {
             return Ok(None);
         }
 
-        let code = &code.instructions[range];
+        let Some(block) = resolve_straight_line(get_code_of_block(meta, code), data, Vec::new())?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(Resolution {
+            nodes: [entry].into_iter().collect(),
+            merged_into: ResolveState::Resolved(block),
+            merged_children: block_cfg.children_of(entry).clone(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Turn a run of instructions with no control flow into statements.
+///
+/// `stack` holds the values left on the stack by whatever ran before. If the
+/// instructions need more values than that, `None` is returned.
+fn resolve_straight_line(
+    code: &[Instruction],
+    data: &GMData,
+    mut stack: Vec<ast::Expr>,
+) -> Result<Option<ResolvedBlock>> {
+    /// Pop a value off the stack, giving up on this block if it is empty.
+    macro_rules! pop {
+        () => {
+            match stack.pop() {
+                Some(v) => v,
+                None => return Ok(None),
+            }
+        };
+    }
 
-        let mut out = Vec::new();
-        let mut stack = Vec::new();
+    let mut out = Vec::new();
+    let mut branch = None;
+    let mut saved_array = None;
 
-        let mut i = 0usize;
+    let mut i = 0usize;
 
-        loop {
-            if i >= code.len() {
-                break;
+    loop {
+        if i >= code.len() {
+            break;
+        }
+        let instr = code[i].clone();
+        match instr {
+            Instruction::Push {
+                value: PushValue::Variable(variable),
             }
-            let instr = code[i].clone();
-            match instr {
-                Instruction::Push { value } => {
-                    stack.push(match value {
-                        PushValue::Boolean(v) => ast::Expr::Constant(Constant::Boolean(v)),
-                        PushValue::Int16(v) => ast::Expr::Constant(Constant::Integer(v as i64)),
-                        PushValue::Int32(v) => ast::Expr::Constant(Constant::Integer(v as i64)),
-                        PushValue::Int64(v) => ast::Expr::Constant(Constant::Integer(v as i64)),
-                        PushValue::Double(v) => ast::Expr::Constant(Constant::Float(v)),
-                        PushValue::String(v) => ast::Expr::Constant(Constant::String(v)),
-                        PushValue::Function(v) => ast::Expr::Ident(
-                            v.resolve(&data.functions.functions).unwrap().name.clone(),
-                        ),
-                        PushValue::Variable(v) => ast::Expr::Ident(
-                            v.variable
-                                .resolve(&data.variables.variables)
-                                .unwrap()
-                                .name
-                                .clone(),
-                        ),
-                    });
-                }
-                Instruction::Add {
-                    augend: _,
-                    addend: _,
-                }
-                | Instruction::And { lhs: _, rhs: _ }
-                | Instruction::Divide {
-                    dividend: _,
-                    divisor: _,
-                }
-                | Instruction::Modulus {
-                    dividend: _,
-                    divisor: _,
-                }
-                | Instruction::Or { lhs: _, rhs: _ }
-                | Instruction::Remainder {
-                    dividend: _,
-                    divisor: _,
-                }
-                | Instruction::ShiftLeft {
-                    value: _,
-                    shift_amount: _,
-                }
-                | Instruction::ShiftRight {
-                    value: _,
-                    shift_amount: _,
-                }
-                | Instruction::Subtract {
-                    minuend: _,
-                    subtrahend: _,
-                }
-                | Instruction::Xor { lhs: _, rhs: _ }
-                | Instruction::Multiply {
-                    multiplicand: _,
-                    multiplier: _,
-                } => {
-                    let (arg2, arg1) = (
-                        stack.pop().ok_or(err!("stack underflow while attempting to resolve straight-line block {entry}"))?,
-                        stack.pop().ok_or(err!("stack underflow while attempting to resolve straight-line block {entry}"))?
-                    );
-
-                    stack.push(ast::Expr::Binary{
-                        lhs: Box::new(arg1),
-                        rhs: Box::new(arg2),
-                        op: match instr {
-                            Instruction::Add {
-                                augend: _,
-                                addend: _,
-                            } => BinaryOp::Add,
-                            Instruction::And {
-                                lhs: DataType::Boolean,
-                                rhs: _,
-                            } => BinaryOp::And,
-                            Instruction::And { lhs: _, rhs: _ } => BinaryOp::BitAnd,
-                            Instruction::Divide {
-                                dividend: DataType::Int16 | DataType::Int32 | DataType::Int64,
-                                divisor: _,
-                            } => BinaryOp::IDiv,
-                            Instruction::Divide {
-                                dividend: _,
-                                divisor: _,
-                            } => BinaryOp::Div,
-                            Instruction::Modulus {
-                                dividend: _,
-                                divisor: _,
-                            }
-                            | Instruction::Remainder {
-                                dividend: _,
-                                divisor: _,
-                            } => BinaryOp::Rem,
-                            Instruction::Or {
-                                lhs: DataType::Boolean,
-                                rhs: _,
-                            } => BinaryOp::Or,
-                            Instruction::Or { lhs: _, rhs: _ } => BinaryOp::BitOr,
-                            Instruction::ShiftLeft {
-                                value: _,
-                                shift_amount: _,
-                            } => BinaryOp::BitShiftLeft,
-                            Instruction::ShiftRight {
-                                value: _,
-                                shift_amount: _,
-                            } => BinaryOp::BitShiftRight,
-                            Instruction::Subtract {
-                                minuend: _,
-                                subtrahend: _,
-                            } => BinaryOp::Sub,
-                            Instruction::Xor {
-                                lhs: DataType::Boolean,
-                                rhs: _,
-                            } => BinaryOp::Xor,
-                            Instruction::Xor { lhs: _, rhs: _ } => BinaryOp::BitXor,
-                            Instruction::Multiply {
-                                multiplicand: _,
-                                multiplier: _,
-                            } => BinaryOp::Mult,
-                            _ => unreachable!(),
-                        },
-                    });
-                }
-                Instruction::Call {
-                    function,
-                    argument_count,
-                } => {
-                    let mut args = Vec::new();
-                    for _ in 0..argument_count {
-                        args.push(stack.pop().unwrap());
+            | Instruction::PushLocal { variable }
+            | Instruction::PushGlobal { variable }
+            | Instruction::PushBuiltin { variable } => {
+                let Some(access) = variable_access(&variable, data, &mut stack)? else {
+                    return Ok(None);
+                };
+                stack.push(passes::mutable_as_expr(&access));
+            }
+            Instruction::PushImmediate { integer } => {
+                stack.push(ast::Expr::Constant(Constant::Integer(i64::from(integer))));
+            }
+            Instruction::Push {
+                value: PushValue::Boolean(v),
+            } => stack.push(ast::Expr::Constant(Constant::Boolean(v))),
+            Instruction::Push {
+                value: PushValue::Int16(v),
+            } => stack.push(ast::Expr::Constant(Constant::Integer(v as i64))),
+            Instruction::Push {
+                value: PushValue::Int32(v),
+            } => stack.push(ast::Expr::Constant(Constant::Integer(v as i64))),
+            Instruction::Push {
+                value: PushValue::Int64(v),
+            } => stack.push(ast::Expr::Constant(Constant::Integer(v as i64))),
+            Instruction::Push {
+                value: PushValue::Double(v),
+            } => stack.push(ast::Expr::Constant(Constant::Float(v))),
+            Instruction::Push {
+                value: PushValue::String(v),
+            } => stack.push(ast::Expr::Constant(Constant::String(v))),
+            Instruction::Push {
+                value: PushValue::Function(v),
+            } => {
+                let name = &v.resolve(&data.functions.functions)?.name;
+                stack.push(ast::Expr::Ident(name.clone()));
+            }
+            Instruction::Add {
+                augend: _,
+                addend: _,
+            }
+            | Instruction::And { lhs: _, rhs: _ }
+            | Instruction::Divide {
+                dividend: _,
+                divisor: _,
+            }
+            | Instruction::Modulus {
+                dividend: _,
+                divisor: _,
+            }
+            | Instruction::Or { lhs: _, rhs: _ }
+            | Instruction::Remainder {
+                dividend: _,
+                divisor: _,
+            }
+            | Instruction::ShiftLeft {
+                value: _,
+                shift_amount: _,
+            }
+            | Instruction::ShiftRight {
+                value: _,
+                shift_amount: _,
+            }
+            | Instruction::Subtract {
+                minuend: _,
+                subtrahend: _,
+            }
+            | Instruction::Xor { lhs: _, rhs: _ }
+            | Instruction::Multiply {
+                multiplicand: _,
+                multiplier: _,
+            } => {
+                let (arg2, arg1) = (pop!(), pop!());
+
+                stack.push(ast::Expr::Binary {
+                    lhs: Box::new(arg1),
+                    rhs: Box::new(arg2),
+                    op: match instr {
+                        Instruction::Add {
+                            augend: _,
+                            addend: _,
+                        } => BinaryOp::Add,
+                        Instruction::And {
+                            lhs: DataType::Boolean,
+                            rhs: _,
+                        } => BinaryOp::And,
+                        Instruction::And { lhs: _, rhs: _ } => BinaryOp::BitAnd,
+                        Instruction::Divide {
+                            dividend: DataType::Int16 | DataType::Int32 | DataType::Int64,
+                            divisor: _,
+                        } => BinaryOp::IDiv,
+                        Instruction::Divide {
+                            dividend: _,
+                            divisor: _,
+                        } => BinaryOp::Div,
+                        Instruction::Modulus {
+                            dividend: _,
+                            divisor: _,
+                        }
+                        | Instruction::Remainder {
+                            dividend: _,
+                            divisor: _,
+                        } => BinaryOp::Rem,
+                        Instruction::Or {
+                            lhs: DataType::Boolean,
+                            rhs: _,
+                        } => BinaryOp::Or,
+                        Instruction::Or { lhs: _, rhs: _ } => BinaryOp::BitOr,
+                        Instruction::ShiftLeft {
+                            value: _,
+                            shift_amount: _,
+                        } => BinaryOp::BitShiftLeft,
+                        Instruction::ShiftRight {
+                            value: _,
+                            shift_amount: _,
+                        } => BinaryOp::BitShiftRight,
+                        Instruction::Subtract {
+                            minuend: _,
+                            subtrahend: _,
+                        } => BinaryOp::Sub,
+                        Instruction::Xor {
+                            lhs: DataType::Boolean,
+                            rhs: _,
+                        } => BinaryOp::Xor,
+                        Instruction::Xor { lhs: _, rhs: _ } => BinaryOp::BitXor,
+                        Instruction::Multiply {
+                            multiplicand: _,
+                            multiplier: _,
+                        } => BinaryOp::Mult,
+                        _ => unreachable!(),
+                    },
+                });
+            }
+            Instruction::Compare {
+                lhs: _,
+                rhs: _,
+                comparison_type,
+            } => {
+                let (rhs, lhs) = (pop!(), pop!());
+                stack.push(ast::Expr::Binary {
+                    lhs: Box::new(lhs),
+                    op: match comparison_type {
+                        ComparisonType::LessThan => BinaryOp::LessThan,
+                        ComparisonType::LessOrEqual => BinaryOp::LessEqual,
+                        ComparisonType::Equal => BinaryOp::Equal,
+                        ComparisonType::NotEqual => BinaryOp::NotEqual,
+                        ComparisonType::GreaterOrEqual => BinaryOp::GreaterEqual,
+                        ComparisonType::GreaterThan => BinaryOp::GreaterThan,
+                    },
+                    rhs: Box::new(rhs),
+                });
+            }
+            // a duplicated value is the same expression twice on the stack.
+            // the compiler only ever emits these where the copies end up in
+            // one construct in the source (a switch, compound assignment or
+            // increment), and the resolvers and passes building those only
+            // keep one of them
+            //
+            // the sizes of `dup` count values of its data type, and `size` is
+            // one less than how many are copied: `dup.v 0` copies a variable
+            // and `dup.i 1` the two ints of an array's instance and index. the
+            // compiler only duplicates values of that one type at a time, so
+            // every value counted is one value on the stack here
+            Instruction::Duplicate { data_type: _, size } => {
+                let count = usize::from(size) + 1;
+                let Some(start) = stack.len().checked_sub(count) else {
+                    return Ok(None);
+                };
+                stack.extend_from_within(start..);
+            }
+            // moves the top `size1` values below the `size2` values under them,
+            // both counted in values of the data type like for `dup`. this is
+            // the `dup` of 2.3 with a second size, which swaps instead
+            Instruction::DuplicateSwap {
+                data_type: _,
+                size1,
+                size2,
+            } => {
+                let (top, bottom) = (usize::from(size1), usize::from(size2));
+                let Some(start) = stack.len().checked_sub(top + bottom) else {
+                    return Ok(None);
+                };
+                stack[start..].rotate_right(top);
+            }
+            Instruction::Negate { data_type: _ } => {
+                let target = pop!();
+                stack.push(ast::Expr::Unary {
+                    op: ast::UnaryOp::Minus,
+                    target: Box::new(target),
+                });
+            }
+            // `not` is a logical not on booleans, and a bitwise one on
+            // everything else
+            Instruction::Not { data_type } => {
+                let target = pop!();
+                stack.push(ast::Expr::Unary {
+                    op: match data_type {
+                        DataType::Boolean => ast::UnaryOp::Not,
+                        _ => ast::UnaryOp::BitNegate,
+                    },
+                    target: Box::new(target),
+                });
+            }
+            Instruction::Call {
+                function,
+                argument_count,
+            } => {
+                let Some(arguments) = pop_arguments(&mut stack, usize::from(argument_count)) else {
+                    return Ok(None);
+                };
+                let name = &function.resolve(&data.functions.functions)?.name;
+                stack.push(match (name.as_str(), arguments.as_slice()) {
+                    // the instances a method is called on are pushed through
+                    // these
+                    ("@@This@@", []) => ast::Expr::This,
+                    ("@@Other@@", []) => ast::Expr::Other,
+                    ("@@Global@@", []) => ast::Expr::Global,
+                    ("@@NewGMLArray@@", _) => ast::Expr::Array(arguments),
+                    // the constructor is passed before its arguments
+                    ("@@NewGMLObject@@", [_, ..]) => {
+                        let mut arguments = arguments;
+                        let constructor = arguments.remove(0);
+                        ast::Expr::Call(ast::Call {
+                            base: Box::new(source_function_ref(constructor)),
+                            arguments,
+                            has_new: true,
+                        })
                     }
-                    stack.push(ast::Expr::Call(ast::Call {
-                        base: Box::new(ast::Expr::Ident(
-                            function
-                                .resolve(&data.functions.functions)
-                                .unwrap()
-                                .name
-                                .clone(),
-                        )),
-                        arguments: args,
+                    _ => ast::Expr::Call(ast::Call {
+                        base: Box::new(ast::Expr::Ident(name.clone())),
+                        arguments,
                         has_new: false,
-                    }));
-                }
-                Instruction::PushReference { asset_reference } => {
-                    stack.push(ast::Expr::Ident(match asset_reference {
-                        AssetReference::Object(gmref) => gmref
-                            .resolve(&data.game_objects.game_objects)
-                            .unwrap()
-                            .name
-                            .clone(),
-                        AssetReference::Sprite(gmref) => {
-                            gmref.resolve(&data.sprites.sprites).unwrap().name.clone()
-                        }
-                        AssetReference::Sound(gmref) => {
-                            gmref.resolve(&data.sounds.sounds).unwrap().name.clone()
-                        }
-                        AssetReference::Room(gmref) => {
-                            gmref.resolve(&data.rooms.rooms).unwrap().name.clone()
-                        }
-                        AssetReference::Path(gmref) => {
-                            gmref.resolve(&data.paths.paths).unwrap().name.clone()
-                        }
-                        AssetReference::Script(gmref) => {
-                            gmref.resolve(&data.scripts.scripts).unwrap().name.clone()
-                        }
-                        AssetReference::Font(gmref) => {
-                            gmref.resolve(&data.fonts.fonts).unwrap().name.clone()
-                        }
-                        AssetReference::Timeline(gmref) => gmref
-                            .resolve(&data.timelines.timelines)
-                            .unwrap()
-                            .name
-                            .clone(),
-                        AssetReference::Shader(gmref) => {
-                            gmref.resolve(&data.shaders.shaders).unwrap().name.clone()
-                        }
-                        AssetReference::Sequence(gmref) => gmref
-                            .resolve(&data.sequences.sequences)
-                            .unwrap()
-                            .name
-                            .clone(),
-                        AssetReference::AnimCurve(gmref) => gmref
-                            .resolve(&data.animation_curves.animation_curves)
-                            .unwrap()
-                            .name
-                            .clone(),
-                        AssetReference::ParticleSystem(gmref) => gmref
-                            .resolve(&data.particle_systems.particle_systems)
-                            .unwrap()
-                            .name
-                            .clone(),
-                        AssetReference::Background(gmref) => gmref
-                            .resolve(&data.backgrounds.backgrounds)
-                            .unwrap()
-                            .name
-                            .clone(),
-                        AssetReference::RoomInstance(v) => format!("inst_{v:X}"),
-                        AssetReference::Function(gmref) => gmref
-                            .resolve(&data.functions.functions)
-                            .unwrap()
-                            .name
-                            .clone(),
-                    }))
-                }
-                Instruction::Exit => {
-                    out.push(ast::Statement::Return(None));
-                }
-                Instruction::Return => {
-                    let val = stack.pop().unwrap();
-                    out.push(ast::Statement::Return(Some(Box::new(val))));
+                    }),
+                });
+            }
+            // calls to functions stored in variables, including methods
+            //
+            // the function goes on top of the instance it's called on, which
+            // is already part of how the function was looked up
+            Instruction::CallVariable { argument_count } => {
+                let function = pop!();
+                let _instance = pop!();
+                let Some(arguments) = pop_arguments(&mut stack, usize::from(argument_count)) else {
+                    return Ok(None);
+                };
+                stack.push(ast::Expr::Call(ast::Call {
+                    base: Box::new(function),
+                    arguments,
+                    has_new: false,
+                }));
+            }
+            Instruction::PushReference { asset_reference } => {
+                stack.push(ast::Expr::Ident(match asset_reference {
+                    AssetReference::Object(gmref) => gmref
+                        .resolve(&data.game_objects.game_objects)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    AssetReference::Sprite(gmref) => {
+                        gmref.resolve(&data.sprites.sprites).unwrap().name.clone()
+                    }
+                    AssetReference::Sound(gmref) => {
+                        gmref.resolve(&data.sounds.sounds).unwrap().name.clone()
+                    }
+                    AssetReference::Room(gmref) => {
+                        gmref.resolve(&data.rooms.rooms).unwrap().name.clone()
+                    }
+                    AssetReference::Path(gmref) => {
+                        gmref.resolve(&data.paths.paths).unwrap().name.clone()
+                    }
+                    AssetReference::Script(gmref) => {
+                        gmref.resolve(&data.scripts.scripts).unwrap().name.clone()
+                    }
+                    AssetReference::Font(gmref) => {
+                        gmref.resolve(&data.fonts.fonts).unwrap().name.clone()
+                    }
+                    AssetReference::Timeline(gmref) => gmref
+                        .resolve(&data.timelines.timelines)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    AssetReference::Shader(gmref) => {
+                        gmref.resolve(&data.shaders.shaders).unwrap().name.clone()
+                    }
+                    AssetReference::Sequence(gmref) => gmref
+                        .resolve(&data.sequences.sequences)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    AssetReference::AnimCurve(gmref) => gmref
+                        .resolve(&data.animation_curves.animation_curves)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    AssetReference::ParticleSystem(gmref) => gmref
+                        .resolve(&data.particle_systems.particle_systems)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    AssetReference::Background(gmref) => gmref
+                        .resolve(&data.backgrounds.backgrounds)
+                        .unwrap()
+                        .name
+                        .clone(),
+                    AssetReference::RoomInstance(v) => format!("inst_{v:X}"),
+                    AssetReference::Function(gmref) => gmref
+                        .resolve(&data.functions.functions)
+                        .unwrap()
+                        .name
+                        .clone(),
+                }))
+            }
+            Instruction::Exit => {
+                out.push(ast::Statement::Return(None));
+            }
+            Instruction::PopDiscard { data_type: _ } => {
+                // nothing but a call has side effects, so there's nothing else
+                // worth keeping
+                if let ast::Expr::Call(call) = pop!() {
+                    out.push(ast::Statement::Call(call));
                 }
-                Instruction::Pop {
-                    variable,
-                    type1: _,
-                    type2: _,
-                } => {
-                    let val = stack.pop().unwrap();
-                    out.push(ast::Statement::Assignment{
-                        target: ast::MutableExpr::Ident(
-                            variable
-                                .variable
-                                .resolve(&data.variables.variables)?
-                                .name
-                                .clone(),
-                        ),
+            }
+            Instruction::Return => {
+                let val = pop!();
+                out.push(ast::Statement::Return(Some(Box::new(val))));
+            }
+            Instruction::Pop {
+                variable,
+                type1,
+                type2: _,
+            } => {
+                // the value usually goes below the instance, but `pop.i.v` has
+                // it on top instead
+                let value_first = matches!(type1, DataType::Int32).then(|| stack.pop());
+                let Some(target) = variable_access(&variable, data, &mut stack)? else {
+                    return Ok(None);
+                };
+                let val = match value_first {
+                    Some(Some(v)) => v,
+                    Some(None) => return Ok(None),
+                    None => pop!(),
+                };
+                match find_mutation(&code[..i], &target, &val) {
+                    Some((op, MutationForm::Statement)) => {
+                        out.push(ast::Statement::Postfix(ast::Mutation {
+                            op,
+                            target: Box::new(target),
+                        }));
+                    }
+                    // the duplicated value is left behind as the result
+                    Some((op, MutationForm::Prefix)) => {
+                        pop!();
+                        stack.push(ast::Expr::Prefix(ast::Mutation {
+                            op,
+                            target: Box::new(target),
+                        }));
+                    }
+                    Some((op, MutationForm::Postfix)) => {
+                        pop!();
+                        stack.push(ast::Expr::Postfix(ast::Mutation {
+                            op,
+                            target: Box::new(target),
+                        }));
+                    }
+                    None => out.push(ast::Statement::Assignment {
+                        target,
                         op: ast::AssignmentOp::Equal,
                         value: Box::new(val),
-                    });
+                    }),
                 }
-                Instruction::Branch { jump_offset } => {
-                    i = get_index_from_byte_offset(&code, i, jump_offset)?;
-                }
-                Instruction::BranchIf { jump_offset: _ }
-                | Instruction::BranchUnless { jump_offset: _ } => {
-                    stack.pop();
-                }
-                Instruction::Convert { from: _, to: _ } => {}
-                _ => todo!("{instr:#?}"),
             }
-            i += 1;
+            // control flow is handled by the block CFG
+            Instruction::Branch { jump_offset: _ } => {}
+            Instruction::BranchIf { jump_offset: _ } => {
+                branch = Some(BranchCondition {
+                    value: pop!(),
+                    jump_if: true,
+                });
+            }
+            Instruction::BranchUnless { jump_offset: _ } => {
+                branch = Some(BranchCondition {
+                    value: pop!(),
+                    jump_if: false,
+                });
+            }
+            // the with resolver handles these, and picks the target back up
+            // off of the stack
+            Instruction::PushWithContext { jump_offset: _ }
+            | Instruction::PopWithContext { jump_offset: _ }
+            | Instruction::PopWithContextExit => {}
+            Instruction::Convert { from: _, to: _ } => {}
+            // `a[i][j]`, where the array `a[i]` is already on the stack
+            Instruction::PushArrayFinal | Instruction::PushArrayContainer => {
+                let (index, array) = (pop!(), pop!());
+                stack.push(ast::Expr::Index {
+                    base: Box::new(array),
+                    accessor_type: None,
+                    indexes: vec![index],
+                });
+            }
+            Instruction::PopArrayFinal => {
+                let (index, array) = (pop!(), pop!());
+                let value = pop!();
+                out.push(ast::Statement::Assignment {
+                    target: ast::MutableExpr::Index {
+                        base: Box::new(array),
+                        accessor_type: None,
+                        indexes: vec![index],
+                    },
+                    op: ast::AssignmentOp::Equal,
+                    value: Box::new(value),
+                });
+            }
+            // compound assignments to chained arrays put the array aside while
+            // the value is worked out
+            Instruction::SaveArrayReference => {
+                saved_array = Some(pop!());
+            }
+            Instruction::RestoreArrayReference => {
+                let Some(array) = saved_array.take() else {
+                    return Ok(None);
+                };
+                stack.push(array);
+            }
+            // bounds checks and copy-on-write bookkeeping that don't show up in
+            // the source
+            Instruction::CheckArrayIndex => {}
+            // the function declaration pass turns the check into `static`
+            Instruction::HasStaticInitialized => {
+                stack.push(ast::Expr::Ident(STATIC_INITIALIZED.to_owned()));
+            }
+            Instruction::SetStaticInitialized => {}
+            Instruction::SetArrayOwner => {
+                pop!();
+            }
+            // the checked value stays on the stack for the null-coalesce
+            // resolver to pick up
+            Instruction::IsNullishValue => {
+                let Some(value) = stack.last() else {
+                    return Ok(None);
+                };
+                let check = ast::Expr::Call(ast::Call {
+                    base: Box::new(ast::Expr::Ident(IS_NULLISH.to_owned())),
+                    arguments: vec![value.clone()],
+                    has_new: false,
+                });
+                stack.push(check);
+            }
+            _ => bail!("unsupported instruction {instr:?}"),
         }
-
-        Ok(Some(Resolution {
-            nodes: [entry].into_iter().collect(),
-            merged_into: ResolveState::Resolved(ast::Block(out)),
-            merged_children: block_cfg.children_of(entry).clone(),
-            merged_parents: block_cfg.parents_of(entry).clone(),
-        }))
+        i += 1;
     }
+
+    Ok(Some(ResolvedBlock {
+        body: ast::Block(out),
+        stack,
+        branch,
+        for_step: false,
+    }))
 }
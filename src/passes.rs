@@ -0,0 +1,722 @@
+//! Passes over the output AST that tidy up what the resolvers produce.
+
+use std::collections::HashSet;
+
+use crate::ast;
+
+/// Call `f` on every block nested in `block`, innermost first, and then on
+/// `block` itself.
+pub(crate) fn visit_blocks(block: &mut ast::Block, f: &mut impl FnMut(&mut ast::Block)) {
+    for statement in &mut block.0 {
+        visit_statement_blocks(statement, f);
+    }
+    f(block);
+}
+
+/// Call `f` on every block nested in `statement`, innermost first.
+fn visit_statement_blocks(statement: &mut ast::Statement, f: &mut impl FnMut(&mut ast::Block)) {
+    match statement {
+        ast::Statement::Block(block) | ast::Statement::Function { body: block, .. } => {
+            visit_blocks(block, f);
+        }
+        ast::Statement::If { then, r#else, .. } => {
+            visit_statement_blocks(then, f);
+            if let Some(r#else) = r#else {
+                visit_statement_blocks(r#else, f);
+            }
+        }
+        ast::Statement::For { body, .. } => visit_statement_blocks(body, f),
+        ast::Statement::While(stmt)
+        | ast::Statement::DoUntil(stmt)
+        | ast::Statement::Repeat(stmt)
+        | ast::Statement::With(stmt) => visit_statement_blocks(&mut stmt.body, f),
+        ast::Statement::Switch { cases, .. } => {
+            for case in cases {
+                visit_blocks(&mut case.body, f);
+            }
+        }
+        ast::Statement::TryCatch {
+            try_block,
+            catch,
+            finally_block,
+        } => {
+            visit_statement_blocks(try_block, f);
+            if let Some((_, catch_block)) = catch {
+                visit_statement_blocks(catch_block, f);
+            }
+            if let Some(finally_block) = finally_block {
+                visit_statement_blocks(finally_block, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Call `f` on every expression in `statement`, including the ones in nested
+/// statements, innermost first.
+pub(crate) fn visit_statement_exprs(
+    statement: &mut ast::Statement,
+    f: &mut impl FnMut(&mut ast::Expr),
+) {
+    match statement {
+        ast::Statement::Block(block) => {
+            for statement in &mut block.0 {
+                visit_statement_exprs(statement, f);
+            }
+        }
+        ast::Statement::Enum {
+            variants: values, ..
+        }
+        | ast::Statement::Var(values)
+        | ast::Statement::Static(values) => {
+            for value in values.iter_mut().filter_map(|v| v.1.as_mut()) {
+                visit_expr(value, f);
+            }
+        }
+        ast::Statement::Function {
+            inherit,
+            params,
+            body,
+            ..
+        } => {
+            if let Some(inherit) = inherit {
+                visit_call(inherit, f);
+            }
+            for param in params {
+                visit_expr(&mut param.default, f);
+            }
+            for statement in &mut body.0 {
+                visit_statement_exprs(statement, f);
+            }
+        }
+        ast::Statement::Assignment { target, value, .. } => {
+            visit_mutable_expr(target, f);
+            visit_expr(value, f);
+        }
+        ast::Statement::Return(value) => {
+            if let Some(value) = value {
+                visit_expr(value, f);
+            }
+        }
+        ast::Statement::If { cond, then, r#else } => {
+            visit_expr(cond, f);
+            visit_statement_exprs(then, f);
+            if let Some(r#else) = r#else {
+                visit_statement_exprs(r#else, f);
+            }
+        }
+        ast::Statement::For {
+            initializer,
+            condition,
+            iterator,
+            body,
+        } => {
+            visit_statement_exprs(initializer, f);
+            visit_expr(condition, f);
+            visit_statement_exprs(iterator, f);
+            visit_statement_exprs(body, f);
+        }
+        ast::Statement::While(stmt)
+        | ast::Statement::DoUntil(stmt)
+        | ast::Statement::Repeat(stmt)
+        | ast::Statement::With(stmt) => {
+            visit_expr(&mut stmt.target, f);
+            visit_statement_exprs(&mut stmt.body, f);
+        }
+        ast::Statement::Switch { target, cases } => {
+            visit_expr(target, f);
+            for case in cases {
+                if let Some(compare) = &mut case.compare {
+                    visit_expr(compare, f);
+                }
+                for statement in &mut case.body.0 {
+                    visit_statement_exprs(statement, f);
+                }
+            }
+        }
+        ast::Statement::TryCatch {
+            try_block,
+            catch,
+            finally_block,
+        } => {
+            visit_statement_exprs(try_block, f);
+            if let Some((_, catch_block)) = catch {
+                visit_statement_exprs(catch_block, f);
+            }
+            if let Some(finally_block) = finally_block {
+                visit_statement_exprs(finally_block, f);
+            }
+        }
+        ast::Statement::Throw(value) => visit_expr(value, f),
+        ast::Statement::Call(call) => visit_call(call, f),
+        ast::Statement::Prefix(mutation) | ast::Statement::Postfix(mutation) => {
+            visit_mutable_expr(&mut mutation.target, f);
+        }
+        ast::Statement::Empty
+        | ast::Statement::GlobalVar(_)
+        | ast::Statement::Break
+        | ast::Statement::Continue => {}
+    }
+}
+
+/// Call `f` on every expression nested in `expr`, innermost first, and then on
+/// `expr` itself.
+fn visit_expr(expr: &mut ast::Expr, f: &mut impl FnMut(&mut ast::Expr)) {
+    match expr {
+        ast::Expr::Group(inner) | ast::Expr::Unary { target: inner, .. } => visit_expr(inner, f),
+        ast::Expr::Object(fields) => {
+            for field in fields {
+                if let ast::Field::Value(_, value) = field {
+                    visit_expr(value, f);
+                }
+            }
+        }
+        ast::Expr::Array(values) => {
+            for value in values {
+                visit_expr(value, f);
+            }
+        }
+        ast::Expr::Prefix(mutation) | ast::Expr::Postfix(mutation) => {
+            visit_mutable_expr(&mut mutation.target, f);
+        }
+        ast::Expr::Binary { lhs, rhs, .. } => {
+            visit_expr(lhs, f);
+            visit_expr(rhs, f);
+        }
+        ast::Expr::Ternary {
+            cond,
+            if_true,
+            if_false,
+        } => {
+            visit_expr(cond, f);
+            visit_expr(if_true, f);
+            visit_expr(if_false, f);
+        }
+        ast::Expr::Call(call) => visit_call(call, f),
+        ast::Expr::Field { base, .. } => visit_expr(base, f),
+        ast::Expr::Index { base, indexes, .. } => {
+            visit_expr(base, f);
+            for index in indexes {
+                visit_expr(index, f);
+            }
+        }
+        ast::Expr::Argument { arg_index } => visit_expr(arg_index, f),
+        ast::Expr::Global
+        | ast::Expr::This
+        | ast::Expr::Other
+        | ast::Expr::Constant(_)
+        | ast::Expr::Ident(_)
+        | ast::Expr::ArgumentCount => {}
+    }
+    f(expr);
+}
+
+/// Call `f` on every expression in `target`, innermost first.
+fn visit_mutable_expr(target: &mut ast::MutableExpr, f: &mut impl FnMut(&mut ast::Expr)) {
+    match target {
+        ast::MutableExpr::Ident(_) => {}
+        ast::MutableExpr::Field { base, .. } => visit_expr(base, f),
+        ast::MutableExpr::Index { base, indexes, .. } => {
+            visit_expr(base, f);
+            for index in indexes {
+                visit_expr(index, f);
+            }
+        }
+    }
+}
+
+/// Call `f` on every expression in `call`, innermost first.
+fn visit_call(call: &mut ast::Call, f: &mut impl FnMut(&mut ast::Expr)) {
+    visit_expr(&mut call.base, f);
+    for argument in &mut call.arguments {
+        visit_expr(argument, f);
+    }
+}
+
+/// The functions reading through accessors compile to, with the accessor and
+/// how many indexes it takes.
+const ACCESSOR_GETTERS: [(&str, ast::AccessorType, usize); 4] = [
+    ("ds_list_find_value", ast::AccessorType::List, 1),
+    ("ds_map_find_value", ast::AccessorType::Map, 1),
+    ("ds_grid_get", ast::AccessorType::Grid, 2),
+    ("variable_struct_get", ast::AccessorType::Struct, 1),
+];
+
+/// The functions writing through accessors compile to, with the accessor and
+/// how many indexes it takes. The value comes after the indexes.
+const ACCESSOR_SETTERS: [(&str, ast::AccessorType, usize); 4] = [
+    ("ds_list_set", ast::AccessorType::List, 1),
+    ("ds_map_set", ast::AccessorType::Map, 1),
+    ("ds_grid_set", ast::AccessorType::Grid, 2),
+    ("variable_struct_set", ast::AccessorType::Struct, 1),
+];
+
+/// Turn the function calls accessors compile to back into accessors.
+///
+/// ```text
+/// ds_list_find_value(l, i)  =>  l[| i]
+/// ds_map_set(m, k, v);      =>  m[? k] = v;
+/// ```
+pub(crate) fn fold_accessors(block: &mut ast::Block) {
+    visit_blocks(block, &mut |block| {
+        for statement in &mut block.0 {
+            if let ast::Statement::Call(call) = statement
+                && let Some((base, accessor_type, indexes, [value])) =
+                    accessor_call(call, &ACCESSOR_SETTERS)
+            {
+                *statement = ast::Statement::Assignment {
+                    target: ast::MutableExpr::Index {
+                        base: Box::new(base),
+                        accessor_type: Some(accessor_type),
+                        indexes,
+                    },
+                    op: ast::AssignmentOp::Equal,
+                    value: Box::new(value.clone()),
+                };
+            }
+        }
+    });
+
+    for statement in &mut block.0 {
+        visit_statement_exprs(statement, &mut |expr| {
+            if let ast::Expr::Call(call) = expr
+                && let Some((base, accessor_type, indexes, [])) =
+                    accessor_call(call, &ACCESSOR_GETTERS)
+            {
+                *expr = ast::Expr::Index {
+                    base: Box::new(base),
+                    accessor_type: Some(accessor_type),
+                    indexes,
+                };
+            }
+        });
+    }
+}
+
+/// If `call` calls one of the functions in `functions`, split its arguments
+/// into the accessed value, the indexes and the arguments left over.
+fn accessor_call<'a>(
+    call: &'a ast::Call,
+    functions: &[(&str, ast::AccessorType, usize)],
+) -> Option<(
+    ast::Expr,
+    ast::AccessorType,
+    Vec<ast::Expr>,
+    &'a [ast::Expr],
+)> {
+    let ast::Expr::Ident(name) = call.base.as_ref() else {
+        return None;
+    };
+    let (_, accessor_type, index_count) = functions.iter().find(|v| v.0 == name)?;
+    let (base, arguments) = call.arguments.split_first()?;
+    if call.has_new || arguments.len() < *index_count {
+        return None;
+    }
+    let (indexes, rest) = arguments.split_at(*index_count);
+    Some((base.clone(), *accessor_type, indexes.to_vec(), rest))
+}
+
+/// Turn assignments of an operation on the assigned variable back into
+/// compound assignments.
+///
+/// ```text
+/// x = x + 1;  =>  x += 1;
+/// ```
+pub(crate) fn fold_compound_assignments(block: &mut ast::Block) {
+    visit_blocks(block, &mut |block| {
+        for statement in &mut block.0 {
+            fold_compound(statement);
+            // the parts of a `for` loop aren't in a block
+            if let ast::Statement::For {
+                initializer,
+                iterator,
+                ..
+            } = statement
+            {
+                fold_compound(initializer);
+                fold_compound(iterator);
+            }
+        }
+    });
+}
+
+/// Turn `statement` into a compound assignment if it is one written out.
+fn fold_compound(statement: &mut ast::Statement) {
+    let ast::Statement::Assignment {
+        target,
+        op: op @ ast::AssignmentOp::Equal,
+        value,
+    } = statement
+    else {
+        return;
+    };
+    let ast::Expr::Binary {
+        lhs,
+        op: binary_op,
+        rhs,
+    } = value.as_mut()
+    else {
+        return;
+    };
+    let compound_op = match binary_op {
+        ast::BinaryOp::Add => ast::AssignmentOp::PlusEqual,
+        ast::BinaryOp::Sub => ast::AssignmentOp::MinusEqual,
+        ast::BinaryOp::Mult => ast::AssignmentOp::MultEqual,
+        ast::BinaryOp::Div => ast::AssignmentOp::DivEqual,
+        ast::BinaryOp::Rem => ast::AssignmentOp::RemEqual,
+        ast::BinaryOp::BitAnd => ast::AssignmentOp::BitAndEqual,
+        ast::BinaryOp::BitOr => ast::AssignmentOp::BitOrEqual,
+        ast::BinaryOp::BitXor => ast::AssignmentOp::BitXorEqual,
+        _ => return,
+    };
+    if **lhs != mutable_as_expr(target) {
+        return;
+    }
+
+    *op = compound_op;
+    *value = rhs.clone();
+}
+
+/// The expression reading the value of `target`.
+pub(crate) fn mutable_as_expr(target: &ast::MutableExpr) -> ast::Expr {
+    match target.clone() {
+        ast::MutableExpr::Ident(name) => ast::Expr::Ident(name),
+        ast::MutableExpr::Field { base, field } => ast::Expr::Field { base, field },
+        ast::MutableExpr::Index {
+            base,
+            accessor_type,
+            indexes,
+        } => ast::Expr::Index {
+            base,
+            accessor_type,
+            indexes,
+        },
+    }
+}
+
+/// Give the arguments of a function the names of its parameters `params`, in
+/// place of the `argument0`, `argument1`, ... they are compiled to.
+pub(crate) fn name_arguments(block: &mut ast::Block, params: &[String]) {
+    for statement in &mut block.0 {
+        visit_statement_exprs(statement, &mut |expr| match expr {
+            ast::Expr::Ident(name) => name_argument(name, params),
+            ast::Expr::Prefix(mutation) | ast::Expr::Postfix(mutation) => {
+                name_argument_target(&mut mutation.target, params);
+            }
+            _ => {}
+        });
+    }
+    // the variables assigned to aren't expressions
+    visit_blocks(block, &mut |block| {
+        for statement in &mut block.0 {
+            name_statement_target(statement, params);
+            if let ast::Statement::For {
+                initializer,
+                iterator,
+                ..
+            } = statement
+            {
+                name_statement_target(initializer, params);
+                name_statement_target(iterator, params);
+            }
+        }
+    });
+}
+
+/// Rename the variable `statement` assigns to if it's an argument.
+fn name_statement_target(statement: &mut ast::Statement, params: &[String]) {
+    match statement {
+        ast::Statement::Assignment { target, .. } => name_argument_target(target, params),
+        ast::Statement::Prefix(mutation) | ast::Statement::Postfix(mutation) => {
+            name_argument_target(&mut mutation.target, params);
+        }
+        _ => {}
+    }
+}
+
+/// Rename `target` if it's an argument.
+fn name_argument_target(target: &mut ast::MutableExpr, params: &[String]) {
+    if let ast::MutableExpr::Ident(name) = target {
+        name_argument(name, params);
+    }
+}
+
+/// Rename `name` to its parameter in `params` if it's an argument.
+fn name_argument(name: &mut String, params: &[String]) {
+    if let Some(i) = name
+        .strip_prefix("argument")
+        .and_then(|v| v.parse::<usize>().ok())
+        && let Some(param) = params.get(i)
+    {
+        param.clone_into(name);
+    }
+}
+
+/// Turn an assignment followed by a `while` loop ending by changing the same
+/// variable into a `for` loop.
+///
+/// ```text
+/// i = 0;                  for (i = 0; i < n; i++) {
+/// while (i < n) {             ...
+///     ...             =>  }
+///     i++;
+/// }
+/// ```
+///
+/// When the variable is one of `locals` and the loop is the first place it's
+/// used, the loop declares it in its initializer instead. Returns the locals
+/// declared this way.
+pub(crate) fn fold_for_loops(block: &mut ast::Block, locals: &[String]) -> HashSet<String> {
+    visit_blocks(block, &mut |block| {
+        let mut i = 0;
+        while i + 1 < block.0.len() {
+            if let Some(for_loop) = fold_for(&block.0[i], &block.0[i + 1]) {
+                block.0.splice(i..i + 2, [for_loop]);
+            }
+            i += 1;
+        }
+    });
+
+    let mut declared = HashSet::new();
+    declare_loop_counters(block, locals, &mut HashSet::new(), &mut declared);
+    declared
+}
+
+/// Declare the counters of the `for` loops in `block` which are `locals` in
+/// the loops' initializers, going through the code in order and adding the
+/// variables it uses to `used`. A counter used before its loop is left to be
+/// declared with the rest of the locals.
+///
+/// ```text
+/// for (i = 0; i < n; i++) {}  =>  for (var i = 0; i < n; i++) {}
+/// ```
+fn declare_loop_counters(
+    block: &mut ast::Block,
+    locals: &[String],
+    used: &mut HashSet<String>,
+    declared: &mut HashSet<String>,
+) {
+    for statement in &mut block.0 {
+        declare_statement_counters(statement, locals, used, declared);
+    }
+}
+
+/// [`declare_loop_counters`] for a single statement.
+fn declare_statement_counters(
+    statement: &mut ast::Statement,
+    locals: &[String],
+    used: &mut HashSet<String>,
+    declared: &mut HashSet<String>,
+) {
+    if let ast::Statement::For { initializer, .. } = statement
+        && let ast::Statement::Assignment {
+            target: ast::MutableExpr::Ident(name),
+            op: ast::AssignmentOp::Equal,
+            value,
+        } = initializer.as_ref()
+        && locals.contains(name)
+        && !used.contains(name)
+    {
+        let declaration = ast::Statement::Var(vec![(name.clone(), Some(value.as_ref().clone()))]);
+        declared.insert(name.clone());
+        **initializer = declaration;
+    }
+
+    match statement {
+        ast::Statement::Block(block) => declare_loop_counters(block, locals, used, declared),
+        ast::Statement::If { cond, then, r#else } => {
+            mark_used(cond, used);
+            declare_statement_counters(then, locals, used, declared);
+            if let Some(r#else) = r#else {
+                declare_statement_counters(r#else, locals, used, declared);
+            }
+        }
+        ast::Statement::For {
+            initializer,
+            condition,
+            iterator,
+            body,
+        } => {
+            mark_statement_used(initializer, used);
+            mark_used(condition, used);
+            declare_statement_counters(body, locals, used, declared);
+            mark_statement_used(iterator, used);
+        }
+        ast::Statement::While(stmt)
+        | ast::Statement::DoUntil(stmt)
+        | ast::Statement::Repeat(stmt)
+        | ast::Statement::With(stmt) => {
+            mark_used(&stmt.target, used);
+            declare_statement_counters(&mut stmt.body, locals, used, declared);
+        }
+        ast::Statement::Switch { target, cases } => {
+            mark_used(target, used);
+            for case in cases {
+                if let Some(compare) = &case.compare {
+                    mark_used(compare, used);
+                }
+                declare_loop_counters(&mut case.body, locals, used, declared);
+            }
+        }
+        ast::Statement::TryCatch {
+            try_block,
+            catch,
+            finally_block,
+        } => {
+            declare_statement_counters(try_block, locals, used, declared);
+            if let Some((_, catch_block)) = catch {
+                declare_statement_counters(catch_block, locals, used, declared);
+            }
+            if let Some(finally_block) = finally_block {
+                declare_statement_counters(finally_block, locals, used, declared);
+            }
+        }
+        statement => mark_statement_used(statement, used),
+    }
+}
+
+/// Add the variables `statement` reads or changes to `used`.
+fn mark_statement_used(statement: &ast::Statement, used: &mut HashSet<String>) {
+    match statement {
+        ast::Statement::Var(values) => used.extend(values.iter().map(|v| v.0.clone())),
+        statement => used.extend(assigned_ident(statement).map(str::to_owned)),
+    }
+    visit_statement_exprs(&mut statement.clone(), &mut |v| mark_variable_used(v, used));
+}
+
+/// Add the variables `expr` reads or changes to `used`.
+fn mark_used(expr: &ast::Expr, used: &mut HashSet<String>) {
+    visit_expr(&mut expr.clone(), &mut |v| mark_variable_used(v, used));
+}
+
+/// Add the variable `expr` is, or changes, to `used`, without looking into
+/// the expressions nested in it.
+fn mark_variable_used(expr: &ast::Expr, used: &mut HashSet<String>) {
+    match expr {
+        ast::Expr::Ident(name) => {
+            used.insert(name.clone());
+        }
+        ast::Expr::Prefix(mutation) | ast::Expr::Postfix(mutation) => {
+            if let ast::MutableExpr::Ident(name) = mutation.target.as_ref() {
+                used.insert(name.clone());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Build a `for` loop out of `init` and `while_loop` if they make one.
+///
+/// A `for` loop the resolvers already found, which has no initializer yet, can
+/// also take `init` as its initializer.
+fn fold_for(init: &ast::Statement, while_loop: &ast::Statement) -> Option<ast::Statement> {
+    let name = assigned_ident(init)?;
+    let initializer = init.clone();
+
+    if let ast::Statement::For {
+        initializer: existing,
+        condition,
+        iterator,
+        body,
+    } = while_loop
+        && matches!(existing.as_ref(), ast::Statement::Empty)
+        && mentions(condition, name)
+        && assigned_ident(iterator)? == name
+    {
+        return Some(ast::Statement::For {
+            initializer: Box::new(initializer),
+            condition: condition.clone(),
+            iterator: iterator.clone(),
+            body: body.clone(),
+        });
+    }
+
+    let ast::Statement::While(ast::LoopStmt { target, body }) = while_loop else {
+        return None;
+    };
+    let ast::Statement::Block(body) = body.as_ref() else {
+        return None;
+    };
+    let (iterator, body) = body.0.split_last()?;
+    // `continue` skips the end of a `while` loop, but not a `for` loop's step
+    if !mentions(target, name) || assigned_ident(iterator)? != name || body.iter().any(has_continue)
+    {
+        return None;
+    }
+
+    Some(ast::Statement::For {
+        initializer: Box::new(initializer),
+        condition: target.clone(),
+        iterator: Box::new(iterator.clone()),
+        body: Box::new(ast::Statement::Block(ast::Block(body.to_vec()))),
+    })
+}
+
+/// Whether the variable `name` is read anywhere in `expr`.
+fn mentions(expr: &ast::Expr, name: &str) -> bool {
+    let mut found = false;
+    visit_expr(&mut expr.clone(), &mut |v| {
+        found |= matches!(v, ast::Expr::Ident(v) if v == name);
+    });
+    found
+}
+
+/// Whether `statement` contains a `continue` for the loop it's in, not
+/// counting any loops nested inside it.
+fn has_continue(statement: &ast::Statement) -> bool {
+    match statement {
+        ast::Statement::Continue => true,
+        ast::Statement::Block(block) => block.0.iter().any(has_continue),
+        ast::Statement::If { then, r#else, .. } => {
+            has_continue(then) || r#else.as_deref().is_some_and(has_continue)
+        }
+        ast::Statement::Switch { cases, .. } => {
+            cases.iter().any(|v| v.body.0.iter().any(has_continue))
+        }
+        ast::Statement::TryCatch {
+            try_block,
+            catch,
+            finally_block,
+        } => {
+            has_continue(try_block)
+                || catch.as_ref().is_some_and(|v| has_continue(&v.1))
+                || finally_block.as_deref().is_some_and(has_continue)
+        }
+        _ => false,
+    }
+}
+
+/// The name of the variable a statement changes, if it is a plain identifier.
+fn assigned_ident(statement: &ast::Statement) -> Option<&str> {
+    match statement {
+        ast::Statement::Assignment {
+            target: ast::MutableExpr::Ident(name),
+            ..
+        } => Some(name),
+        ast::Statement::Prefix(mutation) | ast::Statement::Postfix(mutation) => {
+            match mutation.target.as_ref() {
+                ast::MutableExpr::Ident(name) => Some(name),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Get the names of the variables the `catch` blocks in `block` store the
+/// exception into, which the `catch` declares on its own.
+pub(crate) fn catch_variables(block: &mut ast::Block) -> HashSet<String> {
+    let mut names = HashSet::new();
+    visit_blocks(block, &mut |block| {
+        for statement in &block.0 {
+            if let ast::Statement::TryCatch {
+                catch: Some((name, _)),
+                ..
+            } = statement
+            {
+                names.insert(name.clone());
+            }
+        }
+    });
+    names
+}
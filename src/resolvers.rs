@@ -0,0 +1,23 @@
+//! Resolvers for structured control flow constructs.
+
+mod if_else;
+mod jumps;
+mod loops;
+mod null_coalesce;
+mod repeat;
+mod short_circuit;
+mod switch;
+mod ternary;
+mod try_catch;
+mod with;
+
+pub(crate) use if_else::IfResolver;
+pub(crate) use jumps::JumpResolver;
+pub(crate) use loops::{DoUntilResolver, WhileResolver};
+pub(crate) use null_coalesce::{IS_NULLISH, NullCoalesceResolver};
+pub(crate) use repeat::RepeatResolver;
+pub(crate) use short_circuit::ShortCircuitResolver;
+pub(crate) use switch::SwitchResolver;
+pub(crate) use ternary::TernaryResolver;
+pub(crate) use try_catch::{TRY_HOOK, TryResolver, is_call_to};
+pub(crate) use with::WithResolver;
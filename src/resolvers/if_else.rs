@@ -0,0 +1,174 @@
+//! `if` and `if`/`else` statements.
+
+use std::collections::HashSet;
+
+use libgm::{gml::GMCode, prelude::*};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast,
+    cfg::{ControlFlowGraph, NodeRef},
+    split_branch,
+};
+
+/// Resolves a conditional branch whose paths meet back up into an `if` or
+/// `if`/`else` statement.
+pub(crate) struct IfResolver;
+
+impl Resolver for IfResolver {
+    const SPECIFICITY: i16 = 0;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        _code: &GMCode,
+        _data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some(branch) = &head.branch else {
+            return Ok(None);
+        };
+
+        let children = block_cfg.children_of(entry);
+
+        // both paths go to the same place, so the body is empty
+        if children.len() == 1 && !children.contains(&entry) {
+            return Ok(Some(merge(
+                block_cfg,
+                entry,
+                head,
+                &[],
+                ast::Statement::If {
+                    cond: Box::new(branch.fallthrough_cond()),
+                    then: Box::new(ast::Statement::Block(ast::Block(Vec::new()))),
+                    r#else: None,
+                },
+                children.clone(),
+            )));
+        }
+
+        let Some((fallthrough, target)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+
+        let then_arm = arm(block_cfg, entry, fallthrough);
+        let else_arm = arm(block_cfg, entry, target);
+
+        // if (cond) { ... } else { ... }
+        if let (Some(then_arm), Some(else_arm)) = (then_arm, else_arm)
+            && block_cfg.children_of(fallthrough) == block_cfg.children_of(target)
+            && block_cfg.children_of(fallthrough).len() <= 1
+        {
+            return Ok(Some(merge(
+                block_cfg,
+                entry,
+                head,
+                &[fallthrough, target],
+                ast::Statement::If {
+                    cond: Box::new(branch.fallthrough_cond()),
+                    then: Box::new(ast::Statement::Block(then_arm.body.clone())),
+                    r#else: Some(Box::new(else_statement(else_arm.body.clone()))),
+                },
+                block_cfg.children_of(fallthrough).clone(),
+            )));
+        }
+
+        // if (cond) { ... }, with the body placed right after the condition
+        if let Some(then_arm) = then_arm
+            && block_cfg
+                .children_of(fallthrough)
+                .iter()
+                .all(|v| *v == target)
+        {
+            return Ok(Some(merge(
+                block_cfg,
+                entry,
+                head,
+                &[fallthrough],
+                ast::Statement::If {
+                    cond: Box::new(branch.fallthrough_cond()),
+                    then: Box::new(ast::Statement::Block(then_arm.body.clone())),
+                    r#else: None,
+                },
+                [target].into_iter().collect(),
+            )));
+        }
+
+        // if (cond) { ... }, with the body placed at the branch target. unlike
+        // above, the body has to lead back to the other path so that the exit
+        // of a loop isn't mistaken for the body of an if.
+        if let Some(else_arm) = else_arm
+            && block_cfg.children_of(target).len() == 1
+            && block_cfg.children_of(target).contains(&fallthrough)
+        {
+            return Ok(Some(merge(
+                block_cfg,
+                entry,
+                head,
+                &[target],
+                ast::Statement::If {
+                    cond: Box::new(branch.jump_cond()),
+                    then: Box::new(ast::Statement::Block(else_arm.body.clone())),
+                    r#else: None,
+                },
+                [fallthrough].into_iter().collect(),
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Get the resolved block at `node` if it can be used as one of the arms of an
+/// `if` starting at `entry`.
+fn arm(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    entry: NodeRef,
+    node: NodeRef,
+) -> Option<&ResolvedBlock> {
+    if node == entry || block_cfg.parents_of(node).len() != 1 {
+        return None;
+    }
+    match &block_cfg.meta_of(node).resolve_state {
+        ResolveState::Resolved(block) if block.branch.is_none() && block.stack.is_empty() => {
+            Some(block)
+        }
+        _ => None,
+    }
+}
+
+/// The statement to use for an `else` block, turning `else { if ... }` into
+/// `else if ...`.
+fn else_statement(mut block: ast::Block) -> ast::Statement {
+    if let [ast::Statement::If { .. }] = block.0.as_slice() {
+        return block.0.swap_remove(0);
+    }
+    ast::Statement::Block(block)
+}
+
+/// Build the resolution of `entry` and `arms` into `head` followed by
+/// `statement`.
+fn merge(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    entry: NodeRef,
+    head: &ResolvedBlock,
+    arms: &[NodeRef],
+    statement: ast::Statement,
+    merged_children: HashSet<NodeRef>,
+) -> Resolution {
+    let mut body = head.body.0.clone();
+    body.push(statement);
+
+    Resolution {
+        nodes: [entry].into_iter().chain(arms.iter().copied()).collect(),
+        merged_into: ResolveState::Resolved(ResolvedBlock {
+            body: ast::Block(body),
+            stack: head.stack.clone(),
+            branch: None,
+            for_step: false,
+        }),
+        merged_children,
+        merged_parents: block_cfg.parents_of(entry).clone(),
+    }
+}
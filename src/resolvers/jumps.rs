@@ -0,0 +1,239 @@
+//! `break` and `continue` statements.
+
+use std::collections::HashSet;
+
+use libgm::{
+    gml::{GMCode, Instruction},
+    prelude::*,
+};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast, block_at,
+    cfg::{ControlFlowGraph, NodeRef},
+    get_code_of_block, resolve_straight_line,
+};
+
+/// Resolves an unconditional jump out of the middle of a loop or `switch`
+/// into a `break` or `continue`, removing the edge it made.
+///
+/// This is the last resort when nothing else matches, since plenty of jumps
+/// (like the one over an `else`) are part of other constructs.
+///
+/// A `continue` to the step of a `for` loop first marks the step as one, so
+/// the loop isn't turned into a `while` where `continue` would skip it.
+pub(crate) struct JumpResolver;
+
+impl Resolver for JumpResolver {
+    const SPECIFICITY: i16 = i16::MIN;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let meta = block_cfg.meta_of(entry);
+        let children = block_cfg.children_of(entry);
+        let Some(target) = children.iter().next().copied() else {
+            return Ok(None);
+        };
+        let target_meta = block_cfg.meta_of(target);
+        if children.len() != 1 || target_meta.instr_range.start == meta.instr_range.end {
+            return Ok(None);
+        }
+
+        let block_code = get_code_of_block(meta, code);
+        let (mut body, in_switch) = match &meta.resolve_state {
+            ResolveState::Resolved(block) => {
+                if block.branch.is_some()
+                    || !block.stack.is_empty()
+                    || !matches!(
+                        block_code.last(),
+                        Some(Instruction::Branch { jump_offset: _ })
+                    )
+                {
+                    return Ok(None);
+                }
+                (block.body.0.clone(), false)
+            }
+            // a `continue` inside of a switch has to get rid of the switched
+            // value first
+            ResolveState::Unresolved => {
+                let [
+                    rest @ ..,
+                    Instruction::PopDiscard { data_type: _ },
+                    Instruction::Branch { jump_offset: _ },
+                ] = block_code
+                else {
+                    return Ok(None);
+                };
+                let Some(block) = resolve_straight_line(rest, data, Vec::new())? else {
+                    return Ok(None);
+                };
+                if block.branch.is_some() || !block.stack.is_empty() {
+                    return Ok(None);
+                }
+                (block.body.0, true)
+            }
+        };
+
+        let statement = if !in_switch && is_switch_end(block_cfg, code, entry, target) {
+            ast::Statement::Break
+        } else {
+            let Some(targets) = enclosing_loop(block_cfg, code, entry) else {
+                return Ok(None);
+            };
+            // this is the jump back to the top of the loop itself
+            if entry == targets.latch {
+                return Ok(None);
+            }
+            if !in_switch && targets.breaks.contains(&target) {
+                ast::Statement::Break
+            } else if target == targets.header {
+                ast::Statement::Continue
+            } else if target == targets.latch {
+                if let Some(resolution) = mark_for_step(block_cfg, targets.latch) {
+                    return Ok(Some(resolution));
+                }
+                ast::Statement::Continue
+            } else {
+                return Ok(None);
+            }
+        };
+
+        body.push(statement);
+
+        Ok(Some(Resolution {
+            nodes: [entry].into_iter().collect(),
+            merged_into: ResolveState::Resolved(ResolvedBlock::from_statements(body)),
+            merged_children: HashSet::new(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Where `break` and `continue` can jump to in a loop.
+struct LoopTargets {
+    /// The top of the loop.
+    header: NodeRef,
+    /// The last block in the loop, which jumps back to the header.
+    latch: NodeRef,
+    /// The blocks a `break` can jump to. This is usually just the block after
+    /// the loop, but `with` statements leave through a separate block.
+    breaks: Vec<NodeRef>,
+}
+
+/// Find the innermost loop containing `node`.
+///
+/// Loops are found by their back edges, where a block jumps to a block at or
+/// before itself. The loop covers everything from the header to the last block
+/// jumping back to it.
+fn enclosing_loop(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    code: &GMCode,
+    node: NodeRef,
+) -> Option<LoopTargets> {
+    let start = block_cfg.meta_of(node).instr_range.start;
+
+    let mut innermost: Option<(NodeRef, NodeRef)> = None;
+    for latch in block_cfg.iter_sorted() {
+        let latch_range = &block_cfg.meta_of(latch).instr_range;
+        for header in block_cfg.children_of(latch) {
+            let header_start = block_cfg.meta_of(*header).instr_range.start;
+            if header_start > latch_range.start || header_start > start || latch_range.end <= start
+            {
+                continue;
+            }
+            let is_inner = innermost.is_none_or(|(inner_header, inner_latch)| {
+                let inner_start = block_cfg.meta_of(inner_header).instr_range.start;
+                header_start > inner_start
+                    || (header_start == inner_start
+                        && latch_range.end > block_cfg.meta_of(inner_latch).instr_range.end)
+            });
+            if is_inner {
+                innermost = Some((*header, latch));
+            }
+        }
+    }
+    let (header, latch) = innermost?;
+
+    let end = block_cfg.meta_of(latch).instr_range.end;
+    let mut breaks = Vec::new();
+    if let Some(after) = block_at(block_cfg, end) {
+        breaks.push(after);
+
+        // with statements jump over their own exit for `break`
+        let after_meta = block_cfg.meta_of(after);
+        if matches!(
+            get_code_of_block(after_meta, code),
+            [Instruction::Branch { jump_offset: _ }]
+        ) && let Some(exit) = block_at(block_cfg, after_meta.instr_range.end)
+            && matches!(
+                get_code_of_block(block_cfg.meta_of(exit), code).first(),
+                Some(Instruction::PopWithContextExit)
+            )
+        {
+            breaks.push(exit);
+        }
+    }
+
+    Some(LoopTargets {
+        header,
+        latch,
+        breaks,
+    })
+}
+
+/// Whether `target` is the end of a `switch` statement containing `node`, which
+/// pops the switched value.
+///
+/// The jump to `default` or the end after the last `bt` of the compare chain
+/// isn't a `break`, and is left for the switch resolver.
+fn is_switch_end(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    code: &GMCode,
+    node: NodeRef,
+    target: NodeRef,
+) -> bool {
+    let meta = block_cfg.meta_of(target);
+    let node_start = block_cfg.meta_of(node).instr_range.start;
+    let ends_chain = block_cfg.parents_of(node).iter().any(|v| {
+        let parent = block_cfg.meta_of(*v);
+        parent.instr_range.end == node_start
+            && matches!(
+                get_code_of_block(parent, code).last(),
+                Some(Instruction::BranchIf { jump_offset: _ })
+            )
+    });
+    matches!(meta.resolve_state, ResolveState::Unresolved)
+        && meta.instr_range.start > node_start
+        && !ends_chain
+        && matches!(
+            get_code_of_block(meta, code).first(),
+            Some(Instruction::PopDiscard { data_type: _ })
+        )
+}
+
+/// Mark `latch` as the step of a `for` loop if it looks like one and hasn't
+/// been marked yet.
+fn mark_for_step(block_cfg: &ControlFlowGraph<BlockMeta>, latch: NodeRef) -> Option<Resolution> {
+    let ResolveState::Resolved(block) = &block_cfg.meta_of(latch).resolve_state else {
+        return None;
+    };
+    if block.for_step
+        || block.body.0.len() != 1
+        || block.branch.is_some()
+        || !block.stack.is_empty()
+    {
+        return None;
+    }
+
+    let mut block = block.clone();
+    block.for_step = true;
+    Some(Resolution {
+        nodes: [latch].into_iter().collect(),
+        merged_into: ResolveState::Resolved(block),
+        merged_children: block_cfg.children_of(latch).clone(),
+        merged_parents: block_cfg.parents_of(latch).clone(),
+    })
+}
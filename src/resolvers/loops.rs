@@ -0,0 +1,159 @@
+//! `while` and `do`/`until` loops.
+
+use libgm::{gml::GMCode, prelude::*};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast,
+    cfg::{ControlFlowGraph, NodeRef},
+    split_branch,
+};
+
+/// Resolves a loop tested at the top into a `while` loop, or a `for` loop if
+/// `continue` was found jumping to its step.
+///
+/// ```text
+/// head: <cond>
+///       bf end
+/// body: ...
+///       b head
+/// end:
+/// ```
+pub(crate) struct WhileResolver;
+
+impl Resolver for WhileResolver {
+    const SPECIFICITY: i16 = 100;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        _code: &GMCode,
+        _data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some(branch) = &head.branch else {
+            return Ok(None);
+        };
+        if !head.body.0.is_empty() || !head.stack.is_empty() {
+            return Ok(None);
+        }
+
+        let Some((fallthrough, target)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+
+        let (body_node, exit, cond) = if is_loop_body(block_cfg, entry, fallthrough) {
+            (fallthrough, target, branch.fallthrough_cond())
+        } else if is_loop_body(block_cfg, entry, target) {
+            (target, fallthrough, branch.jump_cond())
+        } else {
+            return Ok(None);
+        };
+
+        let ResolveState::Resolved(body) = &block_cfg.meta_of(body_node).resolve_state else {
+            return Ok(None);
+        };
+
+        // a `for` loop's step has to stay separate from the body, since
+        // `continue` skips to it
+        let statement = if body.for_step
+            && let Some((iterator, rest)) = body.body.0.split_last()
+        {
+            ast::Statement::For {
+                initializer: Box::new(ast::Statement::Empty),
+                condition: Box::new(cond),
+                iterator: Box::new(iterator.clone()),
+                body: Box::new(ast::Statement::Block(ast::Block(rest.to_vec()))),
+            }
+        } else {
+            ast::Statement::While(ast::LoopStmt {
+                target: Box::new(cond),
+                body: Box::new(ast::Statement::Block(body.body.clone())),
+            })
+        };
+
+        Ok(Some(Resolution {
+            nodes: [entry, body_node].into_iter().collect(),
+            merged_into: ResolveState::Resolved(ResolvedBlock::from_statements(vec![statement])),
+            merged_children: [exit].into_iter().collect(),
+            merged_parents: block_cfg
+                .parents_of(entry)
+                .iter()
+                .copied()
+                .filter(|v| *v != body_node)
+                .collect(),
+        }))
+    }
+}
+
+/// Whether `node` is a fully resolved body of a loop with the header `head`.
+fn is_loop_body(block_cfg: &ControlFlowGraph<BlockMeta>, head: NodeRef, node: NodeRef) -> bool {
+    let ResolveState::Resolved(body) = &block_cfg.meta_of(node).resolve_state else {
+        return false;
+    };
+    node != head
+        && body.branch.is_none()
+        && body.stack.is_empty()
+        && block_cfg.parents_of(node).len() == 1
+        && block_cfg.parents_of(node).contains(&head)
+        && block_cfg.children_of(node).len() == 1
+        && block_cfg.children_of(node).contains(&head)
+}
+
+/// Resolves a loop tested at the bottom into a `do`/`until` loop.
+///
+/// ```text
+/// body: ...
+///       <cond>
+///       bf body
+/// end:
+/// ```
+pub(crate) struct DoUntilResolver;
+
+impl Resolver for DoUntilResolver {
+    const SPECIFICITY: i16 = 100;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        _code: &GMCode,
+        _data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(body) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some(branch) = &body.branch else {
+            return Ok(None);
+        };
+        if !body.stack.is_empty() {
+            return Ok(None);
+        }
+
+        let Some((fallthrough, target)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+
+        if target != entry {
+            return Ok(None);
+        }
+
+        Ok(Some(Resolution {
+            nodes: [entry].into_iter().collect(),
+            merged_into: ResolveState::Resolved(ResolvedBlock::from_statements(vec![
+                ast::Statement::DoUntil(ast::LoopStmt {
+                    // the loop ends when the branch falls through
+                    target: Box::new(branch.fallthrough_cond()),
+                    body: Box::new(ast::Statement::Block(body.body.clone())),
+                }),
+            ])),
+            merged_children: [fallthrough].into_iter().collect(),
+            merged_parents: block_cfg
+                .parents_of(entry)
+                .iter()
+                .copied()
+                .filter(|v| *v != entry)
+                .collect(),
+        }))
+    }
+}
@@ -0,0 +1,201 @@
+//! Null-coalescing `??` and `??=`.
+
+use std::collections::HashSet;
+
+use libgm::{
+    gml::{GMCode, Instruction},
+    prelude::*,
+};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver,
+    ast::{self, AssignmentOp, BinaryOp},
+    cfg::{ControlFlowGraph, NodeRef},
+    get_code_of_block, resolve_straight_line, resolve_with_stack, split_branch,
+};
+
+/// Stands in for the result of the instruction checking whether the value on
+/// top of the stack is `undefined` or `pointer_null`, which leaves the value
+/// there.
+pub(crate) const IS_NULLISH: &str = "@@is_nullish@@";
+/// Stands in for the checked value while the fallback pops it off, so it isn't
+/// mistaken for a discarded call.
+const CHECKED: &str = "@@checked@@";
+
+/// Resolves the branch over the fallback value of a `??` or `??=`.
+///
+/// ```text
+/// head:     <lhs>
+///           isnullishvalue
+///           bf end
+/// fallback: popz.v
+///           <rhs>
+/// end:      ...
+/// ```
+///
+/// `??=` assigns the fallback instead of leaving it on the stack, so the value
+/// that was checked has to be thrown away separately when it's kept.
+///
+/// ```text
+/// head:     <lhs>
+///           isnullishvalue
+///           bf skip
+/// fallback: popz.v
+///           <rhs>
+///           pop.v.v <lhs>
+///           b end
+/// skip:     popz.v
+/// end:      ...
+/// ```
+pub(crate) struct NullCoalesceResolver;
+
+impl Resolver for NullCoalesceResolver {
+    const SPECIFICITY: i16 = 240;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some(branch) = &head.branch else {
+            return Ok(None);
+        };
+        if branch.jump_if || !is_nullish_check(&branch.value) {
+            return Ok(None);
+        }
+        let Some((lhs, outer_stack)) = head.stack.split_last() else {
+            return Ok(None);
+        };
+        let Some((fallthrough, target)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+        if *block_cfg.parents_of(fallthrough) != HashSet::from([entry]) {
+            return Ok(None);
+        }
+
+        // the fallback starts by popping the checked value, which can't be
+        // simulated on its own
+        let fallthrough_meta = block_cfg.meta_of(fallthrough);
+        let ResolveState::Resolved(fallback) = &fallthrough_meta.resolve_state else {
+            if !starts_with_popz(fallthrough_meta, code) {
+                return Ok(None);
+            }
+            let Some(block) = resolve_straight_line(
+                get_code_of_block(fallthrough_meta, code),
+                data,
+                vec![ast::Expr::Ident(CHECKED.to_owned())],
+            )?
+            else {
+                return Ok(None);
+            };
+            return Ok(Some(Resolution {
+                nodes: HashSet::from([fallthrough]),
+                merged_into: ResolveState::Resolved(block),
+                merged_children: block_cfg.children_of(fallthrough).clone(),
+                merged_parents: block_cfg.parents_of(fallthrough).clone(),
+            }));
+        };
+        if fallback.branch.is_some() {
+            return Ok(None);
+        }
+
+        let children = block_cfg.children_of(fallthrough);
+        let Some(end) = children.iter().next().copied() else {
+            return Ok(None);
+        };
+        if children.len() != 1 {
+            return Ok(None);
+        }
+
+        let mut body = head.body.0.clone();
+        let mut stack = outer_stack.to_vec();
+        let mut nodes = HashSet::from([entry, fallthrough, end]);
+
+        match (fallback.body.0.as_slice(), fallback.stack.as_slice()) {
+            // lhs ?? rhs
+            ([], [rhs]) => {
+                if end != target
+                    || *block_cfg.parents_of(end) != HashSet::from([entry, fallthrough])
+                    || fallthrough_meta.instr_range.end != block_cfg.meta_of(end).instr_range.start
+                {
+                    return Ok(None);
+                }
+                stack.push(ast::Expr::Binary {
+                    lhs: Box::new(lhs.clone()),
+                    op: BinaryOp::NullCoalesce,
+                    rhs: Box::new(rhs.clone()),
+                });
+            }
+            // lhs ??= rhs
+            (
+                [
+                    ast::Statement::Assignment {
+                        target: assigned,
+                        op: AssignmentOp::Equal,
+                        value,
+                    },
+                ],
+                [],
+            ) => {
+                let skip_meta = block_cfg.meta_of(target);
+                if !matches!(skip_meta.resolve_state, ResolveState::Unresolved)
+                    || !matches!(
+                        get_code_of_block(skip_meta, code),
+                        [Instruction::PopDiscard { data_type: _ }]
+                    )
+                    || *block_cfg.parents_of(target) != HashSet::from([entry])
+                    || *block_cfg.children_of(target) != HashSet::from([end])
+                    || *block_cfg.parents_of(end) != HashSet::from([fallthrough, target])
+                    || skip_meta.instr_range.end != block_cfg.meta_of(end).instr_range.start
+                {
+                    return Ok(None);
+                }
+                body.push(ast::Statement::Assignment {
+                    target: assigned.clone(),
+                    op: AssignmentOp::NullCoalesce,
+                    value: value.clone(),
+                });
+                nodes.insert(target);
+            }
+            _ => return Ok(None),
+        }
+
+        let Some(end_block) = resolve_with_stack(block_cfg.meta_of(end), code, data, stack)? else {
+            return Ok(None);
+        };
+        body.extend(end_block.body.0);
+
+        Ok(Some(Resolution {
+            nodes,
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: ast::Block(body),
+                stack: end_block.stack,
+                branch: end_block.branch,
+                for_step: end_block.for_step,
+            }),
+            merged_children: block_cfg.children_of(end).clone(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Whether `value` is the result of checking if a value is nullish.
+fn is_nullish_check(value: &ast::Expr) -> bool {
+    matches!(
+        value,
+        ast::Expr::Call(ast::Call { base, .. })
+            if matches!(base.as_ref(), ast::Expr::Ident(v) if v == IS_NULLISH)
+    )
+}
+
+/// Whether the first instruction of the block `meta` is a `popz`.
+fn starts_with_popz(meta: &BlockMeta, code: &GMCode) -> bool {
+    matches!(
+        get_code_of_block(meta, code).first(),
+        Some(Instruction::PopDiscard { data_type: _ })
+    )
+}
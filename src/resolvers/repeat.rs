@@ -0,0 +1,164 @@
+//! `repeat` loops.
+
+use std::collections::HashSet;
+
+use libgm::{
+    gml::{GMCode, Instruction, instruction::PushValue},
+    prelude::*,
+};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast,
+    cfg::{ControlFlowGraph, NodeRef},
+    get_code_of_block, resolve_straight_line, split_branch,
+};
+
+/// Resolves the counter kept on the stack by a `repeat` loop.
+///
+/// ```text
+/// head: <count>
+///       dup.i 0
+///       push.e 0
+///       cmp.i.e LTE
+///       bt end
+/// body: ...
+/// tail: ...
+///       push.e 1
+///       sub.i.e
+///       dup.i 0
+///       conv.i.b
+///       bt body
+/// end:  popz.i
+/// ```
+///
+/// `body` and `tail` are the same block when the body has no control flow of
+/// its own.
+pub(crate) struct RepeatResolver;
+
+impl Resolver for RepeatResolver {
+    const SPECIFICITY: i16 = 200;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some((count, outer_stack)) = head.stack.split_last() else {
+            return Ok(None);
+        };
+        if head.branch.is_none() {
+            return Ok(None);
+        }
+
+        let Some((body_start, end)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+
+        // the part of the body before the block holding the counter decrement
+        let (tail, body) = if block_cfg.children_of(body_start).contains(&body_start) {
+            (body_start, None)
+        } else {
+            let ResolveState::Resolved(body) = &block_cfg.meta_of(body_start).resolve_state else {
+                return Ok(None);
+            };
+            let children = block_cfg.children_of(body_start);
+            let Some(tail) = children.iter().next().copied() else {
+                return Ok(None);
+            };
+            if children.len() != 1
+                || body.branch.is_some()
+                || !body.stack.is_empty()
+                || *block_cfg.parents_of(body_start) != HashSet::from([entry, tail])
+                || *block_cfg.parents_of(tail) != HashSet::from([body_start])
+            {
+                return Ok(None);
+            }
+            (tail, Some(body))
+        };
+
+        if *block_cfg.children_of(tail) != HashSet::from([body_start, end])
+            || *block_cfg.parents_of(end) != HashSet::from([entry, tail])
+        {
+            return Ok(None);
+        }
+
+        let tail_meta = block_cfg.meta_of(tail);
+        let end_meta = block_cfg.meta_of(end);
+        if !matches!(tail_meta.resolve_state, ResolveState::Unresolved)
+            || !matches!(end_meta.resolve_state, ResolveState::Unresolved)
+        {
+            return Ok(None);
+        }
+
+        let Some(tail_code) = strip_decrement(get_code_of_block(tail_meta, code)) else {
+            return Ok(None);
+        };
+        let [Instruction::PopDiscard { data_type: _ }, end_code @ ..] =
+            get_code_of_block(end_meta, code)
+        else {
+            return Ok(None);
+        };
+
+        let Some(tail_block) = resolve_straight_line(tail_code, data, Vec::new())? else {
+            return Ok(None);
+        };
+        if tail_block.branch.is_some() || !tail_block.stack.is_empty() {
+            return Ok(None);
+        }
+        let Some(end_block) = resolve_straight_line(end_code, data, outer_stack.to_vec())? else {
+            return Ok(None);
+        };
+
+        let mut loop_body = body.map(|v| v.body.0.clone()).unwrap_or_default();
+        loop_body.extend(tail_block.body.0);
+
+        let mut out = head.body.0.clone();
+        out.push(ast::Statement::Repeat(ast::LoopStmt {
+            target: Box::new(count.clone()),
+            body: Box::new(ast::Statement::Block(ast::Block(loop_body))),
+        }));
+        out.extend(end_block.body.0);
+
+        Ok(Some(Resolution {
+            nodes: [entry, body_start, tail, end].into_iter().collect(),
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: ast::Block(out),
+                stack: end_block.stack,
+                branch: end_block.branch,
+                for_step: false,
+            }),
+            merged_children: block_cfg.children_of(end).clone(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Strip the counter decrement and branch back off the end of the last block
+/// of a `repeat` loop's body, returning the instructions before it.
+fn strip_decrement(code: &[Instruction]) -> Option<&[Instruction]> {
+    let [
+        rest @ ..,
+        Instruction::Push {
+            value: PushValue::Int16(1) | PushValue::Int32(1) | PushValue::Int64(1),
+        }
+        | Instruction::PushImmediate { integer: 1 },
+        Instruction::Subtract {
+            minuend: _,
+            subtrahend: _,
+        },
+        Instruction::Duplicate {
+            data_type: _,
+            size: _,
+        },
+        Instruction::Convert { from: _, to: _ },
+        Instruction::BranchIf { jump_offset: _ },
+    ] = code
+    else {
+        return None;
+    };
+    Some(rest)
+}
@@ -0,0 +1,150 @@
+//! Short-circuiting `&&` and `||`.
+
+use std::collections::HashSet;
+
+use libgm::{gml::GMCode, prelude::*};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver,
+    ast::{self, BinaryOp, Constant},
+    cfg::{ControlFlowGraph, NodeRef},
+    split_branch,
+};
+
+/// Resolves the branches making up a chain of `&&` or `||` into the value they
+/// produce.
+///
+/// ```text
+/// head:  <a>
+///        bf short
+///        <b>
+///        bf short
+///        <c>
+///        b end
+/// short: push.e 0
+/// end:
+/// ```
+///
+/// This is `a && b && c`. `||` is the same, but with `bt` and `push.e 1`.
+pub(crate) struct ShortCircuitResolver;
+
+impl Resolver for ShortCircuitResolver {
+    const SPECIFICITY: i16 = 250;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        _code: &GMCode,
+        _data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some(branch) = &head.branch else {
+            return Ok(None);
+        };
+        let Some((mut next, short)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+
+        let (op, short_value) = if branch.jump_if {
+            (BinaryOp::Or, true)
+        } else {
+            (BinaryOp::And, false)
+        };
+
+        let mut nodes = HashSet::from([entry, short]);
+        let mut chain = HashSet::from([entry]);
+        let mut previous = entry;
+        let mut value = branch.value.clone();
+
+        // every operand but the last branches to `short`
+        let (last, end) = loop {
+            if *block_cfg.parents_of(next) != HashSet::from([previous]) {
+                return Ok(None);
+            }
+            let ResolveState::Resolved(block) = &block_cfg.meta_of(next).resolve_state else {
+                return Ok(None);
+            };
+            if !block.body.0.is_empty() {
+                return Ok(None);
+            }
+
+            match &block.branch {
+                Some(next_branch)
+                    if next_branch.jump_if == branch.jump_if && block.stack.is_empty() =>
+                {
+                    let Some((fallthrough, target)) = split_branch(block_cfg, next) else {
+                        return Ok(None);
+                    };
+                    if target != short {
+                        return Ok(None);
+                    }
+                    value = binary(value, op.clone(), next_branch.value.clone());
+                    nodes.insert(next);
+                    chain.insert(next);
+                    previous = next;
+                    next = fallthrough;
+                }
+                None => {
+                    let [last] = block.stack.as_slice() else {
+                        return Ok(None);
+                    };
+                    let children = block_cfg.children_of(next);
+                    let Some(end) = children.iter().next().copied() else {
+                        return Ok(None);
+                    };
+                    if children.len() != 1 {
+                        return Ok(None);
+                    }
+                    nodes.insert(next);
+                    break (last.clone(), end);
+                }
+                Some(_) => return Ok(None),
+            }
+        };
+
+        // the value pushed when the chain is cut short
+        let ResolveState::Resolved(short_block) = &block_cfg.meta_of(short).resolve_state else {
+            return Ok(None);
+        };
+        let is_short_value = match short_block.stack.as_slice() {
+            [ast::Expr::Constant(Constant::Boolean(v))] => *v == short_value,
+            [ast::Expr::Constant(Constant::Integer(v))] => *v == i64::from(short_value),
+            _ => false,
+        };
+        if !is_short_value
+            || !short_block.body.0.is_empty()
+            || short_block.branch.is_some()
+            || *block_cfg.parents_of(short) != chain
+            || *block_cfg.children_of(short) != HashSet::from([end])
+            || block_cfg.meta_of(short).instr_range.end != block_cfg.meta_of(end).instr_range.start
+        {
+            return Ok(None);
+        }
+
+        let mut stack = head.stack.clone();
+        stack.push(binary(value, op, last));
+
+        Ok(Some(Resolution {
+            nodes,
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: head.body.clone(),
+                stack,
+                branch: None,
+                for_step: false,
+            }),
+            merged_children: HashSet::from([end]),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Build a binary expression.
+fn binary(lhs: ast::Expr, op: BinaryOp, rhs: ast::Expr) -> ast::Expr {
+    ast::Expr::Binary {
+        lhs: Box::new(lhs),
+        op,
+        rhs: Box::new(rhs),
+    }
+}
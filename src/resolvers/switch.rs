@@ -0,0 +1,284 @@
+//! `switch` statements.
+
+use std::collections::HashSet;
+
+use libgm::{
+    gml::{GMCode, Instruction},
+    prelude::*,
+};
+
+use crate::{
+    BlockMeta, BranchCondition, Resolution, ResolveState, ResolvedBlock, Resolver,
+    ast::{self, BinaryOp},
+    block_at,
+    cfg::{ControlFlowGraph, NodeRef},
+    get_code_of_block, resolve_straight_line, split_branch,
+};
+
+/// Resolves the compare chain of a `switch` statement and the case bodies it
+/// jumps into.
+///
+/// ```text
+/// head:    <value>
+///          dup.v 0
+///          <case 1>
+///          cmp.v.v EQ
+///          bt case_1
+///          dup.v 0
+///          <case 2>
+///          cmp.v.v EQ
+///          bt case_2
+///          b default
+/// case_1:  ...
+/// case_2:  ...
+///          b end
+/// default: ...
+/// end:     popz.v
+/// ```
+///
+/// The switched value stays on the stack until `end`. Cases without a `break`
+/// fall through into the next one, `default` included wherever it's written,
+/// and `b end` is used when there is no `default`. A switch with only a `default` is just `b default` after the
+/// value.
+pub(crate) struct SwitchResolver;
+
+impl Resolver for SwitchResolver {
+    const SPECIFICITY: i16 = 300;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some((value, outer_stack)) = head.stack.split_last() else {
+            return Ok(None);
+        };
+
+        // the value of each case and the block it jumps to, in order
+        let mut cases = Vec::new();
+        let mut chain = HashSet::from([entry]);
+        let mut current = entry;
+        let mut branch = head.branch.clone();
+
+        // walk the compare chain until the jump to `default` or `end`
+        let jump = loop {
+            let Some(cond) = branch else {
+                if !ends_with_branch(block_cfg, code, current)
+                    || block_cfg.children_of(current).len() != 1
+                {
+                    return Ok(None);
+                }
+                break current;
+            };
+            let Some(compare) = case_compare(cond) else {
+                return Ok(None);
+            };
+            let Some((fallthrough, target)) = split_branch(block_cfg, current) else {
+                return Ok(None);
+            };
+            cases.push((compare, target));
+
+            if *block_cfg.parents_of(fallthrough) != HashSet::from([current]) {
+                return Ok(None);
+            }
+            let next_meta = block_cfg.meta_of(fallthrough);
+            match &next_meta.resolve_state {
+                // another compare, which needs the value left on the stack
+                ResolveState::Unresolved => {
+                    let Some(next) = resolve_straight_line(
+                        get_code_of_block(next_meta, code),
+                        data,
+                        vec![value.clone()],
+                    )?
+                    else {
+                        return Ok(None);
+                    };
+                    if !next.body.0.is_empty() || next.stack.len() != 1 || next.branch.is_none() {
+                        return Ok(None);
+                    }
+                    branch = next.branch;
+                }
+                // the jump out of the chain
+                ResolveState::Resolved(next)
+                    if next.body.0.is_empty() && next.stack.is_empty() && next.branch.is_none() =>
+                {
+                    branch = None;
+                }
+                ResolveState::Resolved(_) => return Ok(None),
+            }
+            chain.insert(fallthrough);
+            current = fallthrough;
+        };
+
+        let Some(jump_target) = block_cfg.children_of(jump).iter().next().copied() else {
+            return Ok(None);
+        };
+
+        // everything in the switch leads to the block that pops the value,
+        // which comes right after the last case unless it ends with a `break`
+        let mut targets = cases.iter().map(|v| v.1).collect::<HashSet<_>>();
+        targets.insert(jump_target);
+        let mut ends = targets.clone();
+        for target in &targets {
+            ends.extend(block_cfg.children_of(*target).iter().copied());
+        }
+        if let Some(last) = targets.iter().max_by_key(|v| ***v)
+            && let Some(after) = block_at(block_cfg, block_cfg.meta_of(*last).instr_range.end)
+        {
+            ends.insert(after);
+        }
+        ends.retain(|v| {
+            let meta = block_cfg.meta_of(*v);
+            matches!(meta.resolve_state, ResolveState::Unresolved)
+                && matches!(
+                    get_code_of_block(meta, code).first(),
+                    Some(Instruction::PopDiscard { data_type: _ })
+                )
+        });
+        let Some(end) = ends.iter().next().copied() else {
+            return Ok(None);
+        };
+        if ends.len() != 1 {
+            return Ok(None);
+        }
+
+        let default = (jump_target != end).then_some(jump_target);
+
+        targets.remove(&end);
+        let mut bodies = targets.into_iter().collect::<Vec<_>>();
+        bodies.sort_by_key(|v| **v);
+
+        let mut out_cases = Vec::new();
+        for (i, body) in bodies.iter().copied().enumerate() {
+            let next = bodies.get(i + 1).copied().unwrap_or(end);
+            let ResolveState::Resolved(resolved) = &block_cfg.meta_of(body).resolve_state else {
+                return Ok(None);
+            };
+            if resolved.branch.is_some() || !resolved.stack.is_empty() {
+                return Ok(None);
+            }
+
+            let children = block_cfg.children_of(body);
+            let falls_through = next != end && children.contains(&next);
+            if children.iter().any(|v| *v != next && *v != end) {
+                return Ok(None);
+            }
+            let previous = i.checked_sub(1).map(|v| bodies[v]);
+            if block_cfg
+                .parents_of(body)
+                .iter()
+                .any(|v| !chain.contains(v) && Some(*v) != previous)
+            {
+                return Ok(None);
+            }
+
+            let mut statements = resolved.body.0.clone();
+            if *children == HashSet::from([end]) && ends_with_branch(block_cfg, code, body) {
+                statements.push(ast::Statement::Break);
+            }
+
+            // `default` goes where its body is, after the cases sharing it
+            if Some(body) == default {
+                push_cases(&mut out_cases, &cases, body, Vec::new());
+                out_cases.push(ast::SwitchCase {
+                    compare: None,
+                    body: ast::Block(statements),
+                });
+            } else {
+                push_cases(&mut out_cases, &cases, body, statements);
+            }
+        }
+        push_cases(&mut out_cases, &cases, end, Vec::new());
+
+        let end_meta = block_cfg.meta_of(end);
+        let Some(end_block) = resolve_straight_line(
+            &get_code_of_block(end_meta, code)[1..],
+            data,
+            outer_stack.to_vec(),
+        )?
+        else {
+            return Ok(None);
+        };
+
+        let mut out = head.body.0.clone();
+        out.push(ast::Statement::Switch {
+            target: Box::new(value.clone()),
+            cases: out_cases,
+        });
+        out.extend(end_block.body.0);
+
+        let mut nodes = chain;
+        nodes.insert(jump);
+        nodes.insert(end);
+        nodes.extend(bodies);
+
+        Ok(Some(Resolution {
+            nodes,
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: ast::Block(out),
+                stack: end_block.stack,
+                branch: end_block.branch,
+                for_step: false,
+            }),
+            merged_children: block_cfg.children_of(end).clone(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Get the value a case is compared against from the condition of a `bt` in
+/// the compare chain.
+fn case_compare(cond: BranchCondition) -> Option<ast::Expr> {
+    match cond {
+        BranchCondition {
+            value:
+                ast::Expr::Binary {
+                    lhs: _,
+                    op: BinaryOp::Equal,
+                    rhs,
+                },
+            jump_if: true,
+        } => Some(*rhs),
+        _ => None,
+    }
+}
+
+/// Push the cases jumping to `target` onto `out`. Every case but the last
+/// shares its body, so only the last one gets `body`.
+fn push_cases(
+    out: &mut Vec<ast::SwitchCase>,
+    cases: &[(ast::Expr, NodeRef)],
+    target: NodeRef,
+    body: Vec<ast::Statement>,
+) {
+    let compares = cases
+        .iter()
+        .filter(|v| v.1 == target)
+        .map(|v| v.0.clone())
+        .collect::<Vec<_>>();
+    let Some((last, rest)) = compares.split_last() else {
+        return;
+    };
+    for compare in rest {
+        out.push(ast::SwitchCase {
+            compare: Some(compare.clone()),
+            body: ast::Block(Vec::new()),
+        });
+    }
+    out.push(ast::SwitchCase {
+        compare: Some(last.clone()),
+        body: ast::Block(body),
+    });
+}
+
+/// Whether the last instruction of `node` is an unconditional branch.
+fn ends_with_branch(block_cfg: &ControlFlowGraph<BlockMeta>, code: &GMCode, node: NodeRef) -> bool {
+    matches!(
+        get_code_of_block(block_cfg.meta_of(node), code).last(),
+        Some(Instruction::Branch { jump_offset: _ })
+    )
+}
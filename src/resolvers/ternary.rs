@@ -0,0 +1,116 @@
+//! Ternary `? :` expressions.
+
+use std::collections::HashSet;
+
+use libgm::{gml::GMCode, prelude::*};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast,
+    cfg::{ControlFlowGraph, NodeRef},
+    resolve_with_stack, split_branch,
+};
+
+/// Resolves a conditional branch whose paths each push one value and then
+/// meet back up into the ternary expression producing it.
+///
+/// ```text
+/// head:  <cond>
+///        bf false
+/// true:  <if_true>
+///        b end
+/// false: <if_false>
+/// end:   ...
+/// ```
+///
+/// The stack left by each path is what tells this apart from an `if`/`else`
+/// statement, whose paths leave nothing behind.
+pub(crate) struct TernaryResolver;
+
+impl Resolver for TernaryResolver {
+    const SPECIFICITY: i16 = 220;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some(branch) = &head.branch else {
+            return Ok(None);
+        };
+        let Some((fallthrough, target)) = split_branch(block_cfg, entry) else {
+            return Ok(None);
+        };
+
+        let (Some(if_true), Some(if_false)) = (
+            value_arm(block_cfg, entry, fallthrough),
+            value_arm(block_cfg, entry, target),
+        ) else {
+            return Ok(None);
+        };
+
+        // both paths continue at the block right after the jump target
+        let children = block_cfg.children_of(fallthrough);
+        let Some(end) = children.iter().next().copied() else {
+            return Ok(None);
+        };
+        if children.len() != 1
+            || block_cfg.children_of(target) != children
+            || *block_cfg.parents_of(end) != HashSet::from([fallthrough, target])
+            || block_cfg.meta_of(target).instr_range.end != block_cfg.meta_of(end).instr_range.start
+        {
+            return Ok(None);
+        }
+
+        let mut stack = head.stack.clone();
+        stack.push(ast::Expr::Ternary {
+            cond: Box::new(branch.fallthrough_cond()),
+            if_true: Box::new(if_true),
+            if_false: Box::new(if_false),
+        });
+
+        let Some(end_block) = resolve_with_stack(block_cfg.meta_of(end), code, data, stack)? else {
+            return Ok(None);
+        };
+
+        let mut body = head.body.clone();
+        body.0.extend(end_block.body.0);
+
+        Ok(Some(Resolution {
+            nodes: HashSet::from([entry, fallthrough, target, end]),
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body,
+                stack: end_block.stack,
+                branch: end_block.branch,
+                for_step: end_block.for_step,
+            }),
+            merged_children: block_cfg.children_of(end).clone(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Get the value pushed by `node`, one of the paths out of `head`, if that is
+/// all it does.
+fn value_arm(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    head: NodeRef,
+    node: NodeRef,
+) -> Option<ast::Expr> {
+    let ResolveState::Resolved(block) = &block_cfg.meta_of(node).resolve_state else {
+        return None;
+    };
+    let [value] = block.stack.as_slice() else {
+        return None;
+    };
+    if *block_cfg.parents_of(node) != HashSet::from([head])
+        || !block.body.0.is_empty()
+        || block.branch.is_some()
+    {
+        return None;
+    }
+    Some(value.clone())
+}
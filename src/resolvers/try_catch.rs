@@ -0,0 +1,236 @@
+//! `try`, `catch` and `finally` statements.
+
+use std::collections::HashSet;
+
+use libgm::{gml::GMCode, prelude::*};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast,
+    cfg::{ControlFlowGraph, NodeRef},
+    get_code_of_block, resolve_straight_line,
+};
+
+/// The function called at the start of a `try` statement, with the addresses
+/// of its `finally` and `catch` blocks.
+pub(crate) const TRY_HOOK: &str = "@@try_hook@@";
+/// The function called when leaving the `try` block, and when entering the
+/// `catch` block.
+const TRY_UNHOOK: &str = "@@try_unhook@@";
+/// The function called at the end of a `catch` block.
+const FINISH_CATCH: &str = "@@finish_catch@@";
+/// The function called at the end of a `finally` block.
+const FINISH_FINALLY: &str = "@@finish_finally@@";
+/// Stands in for the exception the runtime pushes before jumping into a
+/// `catch` block, until it is assigned to the catch variable.
+const EXCEPTION: &str = "@@exception@@";
+
+/// Resolves a `try` statement from the blocks between its hooks.
+///
+/// ```text
+/// head:    push.i <finally>
+///          conv.i.v
+///          push.i <catch>
+///          conv.i.v
+///          call.i @@try_hook@@(argc=2)
+///          popz.v
+/// try:     ...
+///          call.i @@try_unhook@@(argc=0)
+///          popz.v
+///          b finally
+/// catch:   pop.v.v local.err
+///          call.i @@try_unhook@@(argc=0)
+///          popz.v
+///          ...
+///          call.i @@finish_catch@@(argc=0)
+///          popz.v
+///          b finally
+/// finally: ...
+///          call.i @@finish_finally@@(argc=0)
+///          popz.v
+/// ```
+///
+/// The addresses are `-1` when there is no `catch` or `finally`. Nothing jumps
+/// to the catch block, so the instruction CFG connects it to the hook.
+pub(crate) struct TryResolver;
+
+impl Resolver for TryResolver {
+    const SPECIFICITY: i16 = 280;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let ResolveState::Resolved(head) = &block_cfg.meta_of(entry).resolve_state else {
+            return Ok(None);
+        };
+        let Some((hook, head_body)) = head.body.0.split_last() else {
+            return Ok(None);
+        };
+        if !is_call_to(hook, TRY_HOOK) || head.branch.is_some() || !head.stack.is_empty() {
+            return Ok(None);
+        }
+
+        // the try block falls through from the hook, the catch block doesn't
+        let head_end = block_cfg.meta_of(entry).instr_range.end;
+        let mut try_node = None;
+        let mut catch_node = None;
+        for child in block_cfg.children_of(entry) {
+            if block_cfg.meta_of(*child).instr_range.start == head_end {
+                try_node = Some(*child);
+            } else {
+                catch_node = Some(*child);
+            }
+        }
+        let Some(try_node) = try_node else {
+            return Ok(None);
+        };
+
+        // the catch block starts by popping the exception the runtime pushed,
+        // which can't be simulated on its own
+        if let Some(catch_node) = catch_node {
+            let meta = block_cfg.meta_of(catch_node);
+            if matches!(meta.resolve_state, ResolveState::Unresolved) {
+                let Some(block) = resolve_straight_line(
+                    get_code_of_block(meta, code),
+                    data,
+                    vec![ast::Expr::Ident(EXCEPTION.to_owned())],
+                )?
+                else {
+                    return Ok(None);
+                };
+                return Ok(Some(Resolution {
+                    nodes: HashSet::from([catch_node]),
+                    merged_into: ResolveState::Resolved(block),
+                    merged_children: block_cfg.children_of(catch_node).clone(),
+                    merged_parents: block_cfg.parents_of(catch_node).clone(),
+                }));
+            }
+        }
+
+        let Some(mut try_body) = arm(block_cfg, try_node, entry) else {
+            return Ok(None);
+        };
+        if !try_body.last().is_some_and(|v| is_call_to(v, TRY_UNHOOK)) {
+            return Ok(None);
+        }
+        try_body.pop();
+
+        let mut nodes = HashSet::from([entry, try_node]);
+        let mut after = block_cfg.children_of(try_node).clone();
+
+        let catch = if let Some(catch_node) = catch_node {
+            let Some(catch_body) = arm(block_cfg, catch_node, entry) else {
+                return Ok(None);
+            };
+            let [
+                ast::Statement::Assignment {
+                    target: ast::MutableExpr::Ident(name),
+                    op: ast::AssignmentOp::Equal,
+                    value,
+                },
+                unhook,
+                rest @ ..,
+            ] = catch_body.as_slice()
+            else {
+                return Ok(None);
+            };
+            if !matches!(value.as_ref(), ast::Expr::Ident(v) if v == EXCEPTION)
+                || !is_call_to(unhook, TRY_UNHOOK)
+            {
+                return Ok(None);
+            }
+            let mut rest = rest.to_vec();
+            if rest.last().is_some_and(|v| is_call_to(v, FINISH_CATCH)) {
+                rest.pop();
+            }
+
+            nodes.insert(catch_node);
+            after.extend(block_cfg.children_of(catch_node).iter().copied());
+            Some((name.clone(), block(rest)))
+        } else {
+            None
+        };
+
+        if after.len() > 1 {
+            return Ok(None);
+        }
+
+        let mut out = head_body.to_vec();
+        let mut rest = ResolvedBlock::from_statements(Vec::new());
+        let mut merged_children = after.clone();
+        let mut finally_block = None;
+
+        // the finally block is what both the try and catch blocks lead to,
+        // and ends by calling `@@finish_finally@@`
+        if let Some(next) = after.iter().next().copied()
+            && block_cfg.parents_of(next).iter().all(|v| nodes.contains(v))
+            && let ResolveState::Resolved(next_block) = &block_cfg.meta_of(next).resolve_state
+            && let Some(finish) = next_block
+                .body
+                .0
+                .iter()
+                .position(|v| is_call_to(v, FINISH_FINALLY))
+        {
+            finally_block = Some(block(next_block.body.0[..finish].to_vec()));
+            rest = next_block.clone();
+            rest.body.0.drain(..=finish);
+            merged_children = block_cfg.children_of(next).clone();
+            nodes.insert(next);
+        }
+
+        out.push(ast::Statement::TryCatch {
+            try_block: block(try_body),
+            catch,
+            finally_block,
+        });
+        out.extend(rest.body.0);
+
+        Ok(Some(Resolution {
+            nodes,
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: ast::Block(out),
+                stack: rest.stack,
+                branch: rest.branch,
+                for_step: rest.for_step,
+            }),
+            merged_children,
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Whether `statement` is a call to the function `name` whose result is
+/// discarded.
+pub(crate) fn is_call_to(statement: &ast::Statement, name: &str) -> bool {
+    matches!(
+        statement,
+        ast::Statement::Call(ast::Call { base, .. })
+            if matches!(base.as_ref(), ast::Expr::Ident(v) if v == name)
+    )
+}
+
+/// Get the statements of the try or catch block `node` starting right after
+/// `head`, if it has been fully resolved.
+fn arm(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    node: NodeRef,
+    head: NodeRef,
+) -> Option<Vec<ast::Statement>> {
+    let ResolveState::Resolved(block) = &block_cfg.meta_of(node).resolve_state else {
+        return None;
+    };
+    if *block_cfg.parents_of(node) != HashSet::from([head])
+        || block.branch.is_some()
+        || !block.stack.is_empty()
+    {
+        return None;
+    }
+    Some(block.body.0.clone())
+}
+
+/// Wrap statements into a block statement.
+fn block(statements: Vec<ast::Statement>) -> Box<ast::Statement> {
+    Box::new(ast::Statement::Block(ast::Block(statements)))
+}
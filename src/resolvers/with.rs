@@ -0,0 +1,180 @@
+//! `with` statements.
+
+use std::collections::HashSet;
+
+use libgm::{
+    gml::{GMCode, Instruction},
+    prelude::*,
+};
+
+use crate::{
+    BlockMeta, Resolution, ResolveState, ResolvedBlock, Resolver, ast,
+    cfg::{ControlFlowGraph, NodeRef},
+    get_code_of_block, instance_expr, split_branch,
+};
+
+/// Resolves a `pushenv`/`popenv` pair into a `with` statement.
+///
+/// ```text
+/// head:   <target>
+///         pushenv popenv
+/// body:   ...
+/// popenv: popenv body
+/// ```
+///
+/// If the body contains a `break`, the compiler also emits a separate exit for
+/// it, which has to leave the instance's context with `popenv.e` itself.
+///
+/// ```text
+/// popenv: popenv body
+///         b end
+/// break:  popenv.e
+/// end:
+/// ```
+pub(crate) struct WithResolver;
+
+impl Resolver for WithResolver {
+    const SPECIFICITY: i16 = 200;
+
+    fn try_resolve(
+        block_cfg: &ControlFlowGraph<BlockMeta>,
+        code: &GMCode,
+        data: &GMData,
+        entry: NodeRef,
+    ) -> Result<Option<Resolution>> {
+        let meta = block_cfg.meta_of(entry);
+        let ResolveState::Resolved(head) = &meta.resolve_state else {
+            return Ok(None);
+        };
+        if !matches!(
+            get_code_of_block(meta, code).last(),
+            Some(Instruction::PushWithContext { jump_offset: _ })
+        ) || head.branch.is_some()
+        {
+            return Ok(None);
+        }
+        let Some((target, outer_stack)) = head.stack.split_last() else {
+            return Ok(None);
+        };
+
+        // with an empty body, pushenv jumps to the popenv right after it
+        let children = block_cfg.children_of(entry);
+        let (body, popenv) = if children.len() == 1 {
+            let Some(popenv) = children.iter().next().copied() else {
+                return Ok(None);
+            };
+            (None, popenv)
+        } else {
+            let Some((body, popenv)) = split_branch(block_cfg, entry) else {
+                return Ok(None);
+            };
+            (Some(body), popenv)
+        };
+
+        let popenv_meta = block_cfg.meta_of(popenv);
+        if !matches!(
+            get_code_of_block(popenv_meta, code),
+            [Instruction::PopWithContext { jump_offset: _ }]
+        ) || !matches!(popenv_meta.resolve_state, ResolveState::Resolved(_))
+        {
+            return Ok(None);
+        }
+        let Some((after, back)) = split_branch(block_cfg, popenv) else {
+            return Ok(None);
+        };
+        if back != body.unwrap_or(popenv) {
+            return Ok(None);
+        }
+
+        let mut nodes = HashSet::from([entry, popenv]);
+        let mut exit = after;
+
+        let break_exit = break_exit(block_cfg, code, after);
+        if let Some((jump, break_block, end)) = break_exit {
+            nodes.extend([jump, break_block]);
+            exit = end;
+        }
+
+        let mut statements = Vec::new();
+        if let Some(body) = body {
+            let ResolveState::Resolved(resolved) = &block_cfg.meta_of(body).resolve_state else {
+                return Ok(None);
+            };
+            if resolved.branch.is_some()
+                || !resolved.stack.is_empty()
+                || *block_cfg.parents_of(body) != HashSet::from([entry, popenv])
+            {
+                return Ok(None);
+            }
+
+            let break_block = break_exit.map(|v| v.1);
+            let body_children = block_cfg.children_of(body);
+            if body_children
+                .iter()
+                .any(|v| *v != popenv && Some(*v) != break_block)
+            {
+                return Ok(None);
+            }
+
+            statements.extend(resolved.body.0.iter().cloned());
+            if let Some(break_block) = break_block
+                && body_children.contains(&break_block)
+            {
+                statements.push(ast::Statement::Break);
+            }
+            nodes.insert(body);
+        }
+
+        let mut out = head.body.0.clone();
+        out.push(ast::Statement::With(ast::LoopStmt {
+            target: Box::new(instance_expr(target, data)?),
+            body: Box::new(ast::Statement::Block(ast::Block(statements))),
+        }));
+
+        Ok(Some(Resolution {
+            nodes,
+            merged_into: ResolveState::Resolved(ResolvedBlock {
+                body: ast::Block(out),
+                stack: outer_stack.to_vec(),
+                branch: None,
+                for_step: false,
+            }),
+            merged_children: [exit].into_iter().collect(),
+            merged_parents: block_cfg.parents_of(entry).clone(),
+        }))
+    }
+}
+
+/// Find the exit used for `break` following the `popenv` of a `with`
+/// statement, given the block right after the `popenv`.
+///
+/// Returns the block jumping over the break exit, the break exit itself, and
+/// the block that both of them lead to.
+fn break_exit(
+    block_cfg: &ControlFlowGraph<BlockMeta>,
+    code: &GMCode,
+    jump: NodeRef,
+) -> Option<(NodeRef, NodeRef, NodeRef)> {
+    let jump_meta = block_cfg.meta_of(jump);
+    if !matches!(
+        get_code_of_block(jump_meta, code),
+        [Instruction::Branch { jump_offset: _ }]
+    ) {
+        return None;
+    }
+    let children = block_cfg.children_of(jump);
+    let end = children.iter().next().copied()?;
+    if children.len() != 1 {
+        return None;
+    }
+
+    let break_block = block_cfg.parents_of(end).iter().copied().find(|v| {
+        block_cfg.meta_of(*v).instr_range.start == jump_meta.instr_range.end
+            && matches!(
+                get_code_of_block(block_cfg.meta_of(*v), code),
+                [Instruction::PopWithContextExit]
+            )
+    })?;
+
+    Some((jump, break_block, end))
+}
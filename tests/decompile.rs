@@ -0,0 +1,2144 @@
+//! Decompiling code entries assembled by hand.
+#![allow(clippy::missing_docs_in_private_items)]
+
+use std::collections::HashMap;
+
+use libgm::{
+    gml::{
+        GMCode, GMCodeLocal, GMCodeLocalVariable, GMCodeModernData, Instruction,
+        instruction::{
+            CodeVariable, ComparisonType, DataType, InstanceType, PushValue, VariableType,
+        },
+    },
+    prelude::*,
+};
+use libgmldc::ast::{self, BinaryOp, Constant};
+
+/// A code entry being assembled, along with the data it refers to.
+#[derive(Default)]
+struct Assembler {
+    data: GMData,
+    instructions: Vec<Instruction>,
+    /// The index of the instruction each label is placed before.
+    labels: HashMap<&'static str, usize>,
+    /// The index of each branch and the label it jumps to.
+    jumps: Vec<(usize, &'static str)>,
+    /// The index of each push of an address and the label it's the address of.
+    addresses: Vec<(usize, &'static str)>,
+    /// The name of the code entry of each function declared in the code, the
+    /// label its body starts at and how many arguments it takes.
+    functions: Vec<(&'static str, &'static str, u16)>,
+}
+
+impl Assembler {
+    /// Reference the variable `name` of `instance_type`, adding it to the
+    /// data if it's new.
+    fn variable(&mut self, name: &str, instance_type: InstanceType) -> CodeVariable {
+        self.variable_of(name, VariableType::Normal, instance_type)
+    }
+
+    /// Reference the variable `name` accessed as `variable_type`, adding it
+    /// to the data if it's new.
+    fn variable_of(
+        &mut self,
+        name: &str,
+        variable_type: VariableType,
+        instance_type: InstanceType,
+    ) -> CodeVariable {
+        let variables = &mut self.data.variables.variables;
+        let index = variables
+            .iter()
+            .position(|v| v.name == name)
+            .unwrap_or_else(|| {
+                variables.push(GMVariable {
+                    name: name.to_owned(),
+                    ..Default::default()
+                });
+                variables.len() - 1
+            });
+        CodeVariable {
+            variable: GMRef::new(index as u32),
+            variable_type,
+            instance_type,
+            is_int32: false,
+        }
+    }
+
+    /// Reference the function `name`, adding it to the data if it's new.
+    fn function(&mut self, name: &str) -> GMRef<GMFunction> {
+        let functions = &mut self.data.functions.functions;
+        let index = functions
+            .iter()
+            .position(|v| v.name == name)
+            .unwrap_or_else(|| {
+                functions.push(GMFunction {
+                    name: name.to_owned(),
+                    ..Default::default()
+                });
+                functions.len() - 1
+            });
+        GMRef::new(index as u32)
+    }
+
+    /// Add the object `name` to the data, and get its index.
+    fn object(&mut self, name: &str) -> i16 {
+        let objects = &mut self.data.game_objects.game_objects;
+        objects.push(GMGameObject {
+            name: name.to_owned(),
+            ..Default::default()
+        });
+        (objects.len() - 1) as i16
+    }
+
+    /// Name the locals of the code entry `code`, in order.
+    fn locals(&mut self, code: &str, names: &[&str]) -> &mut Self {
+        self.data.code_locals.code_locals.push(GMCodeLocal {
+            name: code.to_owned(),
+            variables: names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| GMCodeLocalVariable {
+                    index: index as u32,
+                    name: (*name).to_owned(),
+                })
+                .collect(),
+        });
+        self
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> &mut Self {
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Place `label` before the next instruction.
+    fn label(&mut self, label: &'static str) -> &mut Self {
+        self.labels.insert(label, self.instructions.len());
+        self
+    }
+
+    /// Emit the branch `instruction`, jumping to `label` once it's placed.
+    fn jump(&mut self, instruction: Instruction, label: &'static str) -> &mut Self {
+        self.jumps.push((self.instructions.len(), label));
+        self.emit(instruction)
+    }
+
+    fn b(&mut self, label: &'static str) -> &mut Self {
+        self.jump(Instruction::Branch { jump_offset: 0 }, label)
+    }
+
+    fn bt(&mut self, label: &'static str) -> &mut Self {
+        self.jump(Instruction::BranchIf { jump_offset: 0 }, label)
+    }
+
+    fn bf(&mut self, label: &'static str) -> &mut Self {
+        self.jump(Instruction::BranchUnless { jump_offset: 0 }, label)
+    }
+
+    fn pushenv(&mut self, label: &'static str) -> &mut Self {
+        self.jump(Instruction::PushWithContext { jump_offset: 0 }, label)
+    }
+
+    fn popenv(&mut self, label: &'static str) -> &mut Self {
+        self.jump(Instruction::PopWithContext { jump_offset: 0 }, label)
+    }
+
+    fn push_int(&mut self, integer: i16) -> &mut Self {
+        self.emit(Instruction::PushImmediate { integer })
+    }
+
+    /// Push the address of `label` once it's placed.
+    fn push_address(&mut self, label: &'static str) -> &mut Self {
+        self.addresses.push((self.instructions.len(), label));
+        self.emit(Instruction::Push {
+            value: PushValue::Int32(0),
+        })
+    }
+
+    fn push_var(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable(name, InstanceType::Self_(None));
+        self.emit(Instruction::Push {
+            value: PushValue::Variable(variable),
+        })
+    }
+
+    fn pop_var(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable(name, InstanceType::Self_(None));
+        self.emit(Instruction::Pop {
+            variable,
+            type1: DataType::Variable,
+            type2: DataType::Variable,
+        })
+    }
+
+    /// Push the variable `name` of the instance on top of the stack.
+    fn push_field(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable_of(name, VariableType::StackTop, InstanceType::Self_(None));
+        self.emit(Instruction::Push {
+            value: PushValue::Variable(variable),
+        })
+    }
+
+    /// Push the element of the array `name` at the instance and index on top
+    /// of the stack.
+    fn push_array(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable_of(name, VariableType::Array, InstanceType::Self_(None));
+        self.emit(Instruction::Push {
+            value: PushValue::Variable(variable),
+        })
+    }
+
+    fn push_global(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable(name, InstanceType::Global);
+        self.emit(Instruction::PushGlobal { variable })
+    }
+
+    fn push_local(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable(name, InstanceType::Local);
+        self.emit(Instruction::PushLocal { variable })
+    }
+
+    fn pop_local(&mut self, name: &str) -> &mut Self {
+        let variable = self.variable(name, InstanceType::Local);
+        self.emit(Instruction::Pop {
+            variable,
+            type1: DataType::Variable,
+            type2: DataType::Variable,
+        })
+    }
+
+    /// Emit the `++` of what `push` pushes and `pop` stores into, on its own.
+    fn increment(
+        &mut self,
+        push: impl FnOnce(&mut Self) -> &mut Self,
+        pop: impl FnOnce(&mut Self) -> &mut Self,
+    ) -> &mut Self {
+        push(self)
+            .emit(Instruction::Push {
+                value: PushValue::Int16(1),
+            })
+            .emit(Instruction::Add {
+                augend: DataType::Int32,
+                addend: DataType::Variable,
+            });
+        pop(self)
+    }
+
+    fn compare(&mut self, comparison_type: ComparisonType) -> &mut Self {
+        self.emit(Instruction::Compare {
+            lhs: DataType::Int32,
+            rhs: DataType::Variable,
+            comparison_type,
+        })
+    }
+
+    /// Call the function `name` with the `argument_count` values on top of
+    /// the stack, leaving what it returns on the stack.
+    fn call_value(&mut self, name: &str, argument_count: u8) -> &mut Self {
+        let function = self.function(name);
+        self.emit(Instruction::Call {
+            function,
+            argument_count: argument_count.into(),
+        })
+    }
+
+    /// Call the function `name` with the `argument_count` values on top of
+    /// the stack and discard what it returns.
+    fn call(&mut self, name: &str, argument_count: u8) -> &mut Self {
+        self.call_value(name, argument_count)
+            .emit(Instruction::PopDiscard {
+                data_type: DataType::Variable,
+            })
+    }
+
+    /// Declare the function `function`, whose body starts at `label` and
+    /// takes `arguments_count` arguments, by binding it to `self.name`.
+    fn declare(
+        &mut self,
+        function: &'static str,
+        label: &'static str,
+        arguments_count: u16,
+        name: &str,
+    ) -> &mut Self {
+        self.bind(function, label, arguments_count, name, |v| {
+            v.push_int(-1).emit(Instruction::Convert {
+                from: DataType::Int32,
+                to: DataType::Variable,
+            })
+        })
+    }
+
+    /// Declare the constructor `function` like [`Self::declare`], which binds
+    /// it to no instance at all.
+    fn declare_constructor(
+        &mut self,
+        function: &'static str,
+        label: &'static str,
+        arguments_count: u16,
+        name: &str,
+    ) -> &mut Self {
+        self.bind(function, label, arguments_count, name, |v| {
+            v.call_value("@@NullObject@@", 0)
+        })
+    }
+
+    /// Bind `function` to the instance `push_instance` pushes, and assign it
+    /// to `self.name`.
+    fn bind(
+        &mut self,
+        function: &'static str,
+        label: &'static str,
+        arguments_count: u16,
+        name: &str,
+        push_instance: impl FnOnce(&mut Self) -> &mut Self,
+    ) -> &mut Self {
+        self.functions.push((function, label, arguments_count));
+        self.push_function(function);
+        push_instance(self).call_value("method", 2).pop_var(name)
+    }
+
+    fn push_function(&mut self, name: &str) -> &mut Self {
+        let function = self.function(name);
+        self.emit(Instruction::Push {
+            value: PushValue::Function(function),
+        })
+        .emit(Instruction::Convert {
+            from: DataType::Int32,
+            to: DataType::Variable,
+        })
+    }
+
+    /// Start a `try` statement, with its `finally` and `catch` blocks at the
+    /// addresses `push_finally` and `push_catch` push.
+    fn try_hook(
+        &mut self,
+        push_finally: impl FnOnce(&mut Self) -> &mut Self,
+        push_catch: impl FnOnce(&mut Self) -> &mut Self,
+    ) -> &mut Self {
+        let convert = Instruction::Convert {
+            from: DataType::Int32,
+            to: DataType::Variable,
+        };
+        push_finally(self).emit(convert.clone());
+        push_catch(self).emit(convert).call("@@try_hook@@", 2)
+    }
+
+    /// Start a `repeat` loop running the number of times on top of the stack,
+    /// leaving the loop for `end`.
+    fn repeat_head(&mut self, end: &'static str) -> &mut Self {
+        self.emit(Instruction::Duplicate {
+            data_type: DataType::Int32,
+            size: 0,
+        })
+        .emit(Instruction::Push {
+            value: PushValue::Int16(0),
+        })
+        .compare(ComparisonType::LessOrEqual)
+        .bt(end)
+    }
+
+    /// Count down the counter of a `repeat` loop, going back to `body` while
+    /// it's above zero.
+    fn repeat_tail(&mut self, body: &'static str) -> &mut Self {
+        self.emit(Instruction::Push {
+            value: PushValue::Int16(1),
+        })
+        .emit(Instruction::Subtract {
+            minuend: DataType::Int32,
+            subtrahend: DataType::Int32,
+        })
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Int32,
+            size: 0,
+        })
+        .emit(Instruction::Convert {
+            from: DataType::Int32,
+            to: DataType::Boolean,
+        })
+        .bt(body)
+    }
+
+    fn exit(&mut self) -> &mut Self {
+        self.emit(Instruction::Exit)
+    }
+
+    /// Patch the branches to their labels and decompile the code.
+    fn decompile(&mut self) -> Result<ast::Block> {
+        let mut offsets = vec![0i32];
+        for instruction in &self.instructions {
+            offsets.push(offsets[offsets.len() - 1] + instruction.size() as i32);
+        }
+        for (index, label) in &self.addresses {
+            self.instructions[*index] = Instruction::Push {
+                value: PushValue::Int32(offsets[self.labels[label]]),
+            };
+        }
+        for (index, label) in &self.jumps {
+            let offset = (offsets[self.labels[label]] - offsets[*index]) / 4;
+            if let Instruction::Branch { jump_offset }
+            | Instruction::BranchIf { jump_offset }
+            | Instruction::BranchUnless { jump_offset }
+            | Instruction::PushWithContext { jump_offset }
+            | Instruction::PopWithContext { jump_offset } = &mut self.instructions[*index]
+            {
+                *jump_offset = offset;
+            }
+        }
+
+        // the code entries of the functions share the instructions of the
+        // root entry, which goes first
+        let code = GMCode {
+            name: "gml_Script_test".to_owned(),
+            instructions: self.instructions.clone(),
+            modern_data: None,
+        };
+        let mut codes = vec![code.clone()];
+        for (name, label, arguments_count) in &self.functions {
+            codes.push(GMCode {
+                name: (*name).to_owned(),
+                instructions: self.instructions.clone(),
+                modern_data: Some(GMCodeModernData {
+                    arguments_count: *arguments_count,
+                    offset: offsets[self.labels[label]] as u32,
+                    parent: Some(GMRef::new(0)),
+                    ..Default::default()
+                }),
+            });
+        }
+        self.data.codes.codes = codes;
+        libgmldc::decompile(&code, &self.data)
+    }
+}
+
+fn ident(name: &str) -> ast::Expr {
+    ast::Expr::Ident(name.to_owned())
+}
+
+fn int(value: i64) -> ast::Expr {
+    ast::Expr::Constant(Constant::Integer(value))
+}
+
+fn binary(lhs: ast::Expr, op: BinaryOp, rhs: ast::Expr) -> ast::Expr {
+    ast::Expr::Binary {
+        lhs: Box::new(lhs),
+        op,
+        rhs: Box::new(rhs),
+    }
+}
+
+fn assign(name: &str, value: ast::Expr) -> ast::Statement {
+    ast::Statement::Assignment {
+        target: ast::MutableExpr::Ident(name.to_owned()),
+        op: ast::AssignmentOp::Equal,
+        value: Box::new(value),
+    }
+}
+
+fn call(name: &str) -> ast::Statement {
+    ast::Statement::Call(ast::Call {
+        base: Box::new(ident(name)),
+        arguments: Vec::new(),
+        has_new: false,
+    })
+}
+
+fn increment(name: &str) -> ast::Statement {
+    ast::Statement::Postfix(ast::Mutation {
+        op: ast::MutationOp::Increment,
+        target: Box::new(ast::MutableExpr::Ident(name.to_owned())),
+    })
+}
+
+fn block(statements: Vec<ast::Statement>) -> Box<ast::Statement> {
+    Box::new(ast::Statement::Block(ast::Block(statements)))
+}
+
+#[test]
+fn straight_line() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .push_int(1)
+        .emit(Instruction::Add {
+            augend: DataType::Int32,
+            addend: DataType::Variable,
+        })
+        .pop_var("y")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign("y", binary(ident("x"), BinaryOp::Add, int(1))),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn if_else() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .push_int(1)
+        .compare(ComparisonType::Equal)
+        .bf("else")
+        .push_int(1)
+        .pop_var("y")
+        .b("end")
+        .label("else")
+        .push_int(2)
+        .pop_var("y")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::If {
+                cond: Box::new(binary(ident("x"), BinaryOp::Equal, int(1))),
+                then: block(vec![assign("y", int(1))]),
+                r#else: Some(block(vec![assign("y", int(2))])),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn while_loop() -> Result<()> {
+    let out = Assembler::default()
+        .label("head")
+        .push_var("x")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .call("foo", 0)
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::While(ast::LoopStmt {
+                target: Box::new(binary(ident("x"), BinaryOp::LessThan, int(10))),
+                body: block(vec![call("foo")]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn switch() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .push_int(1)
+        .compare(ComparisonType::Equal)
+        .bt("case_1")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .push_int(2)
+        .compare(ComparisonType::Equal)
+        .bt("case_2")
+        .b("end")
+        .label("case_1")
+        .push_int(1)
+        .pop_var("y")
+        .b("end")
+        .label("case_2")
+        .push_int(2)
+        .pop_var("y")
+        .b("end")
+        .label("end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Switch {
+                target: Box::new(ident("x")),
+                cases: vec![
+                    ast::SwitchCase {
+                        compare: Some(int(1)),
+                        body: ast::Block(vec![assign("y", int(1)), ast::Statement::Break]),
+                    },
+                    ast::SwitchCase {
+                        compare: Some(int(2)),
+                        body: ast::Block(vec![assign("y", int(2)), ast::Statement::Break]),
+                    },
+                ],
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn short_circuit() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("a")
+        .bf("short")
+        .push_var("b")
+        .b("end")
+        .label("short")
+        .emit(Instruction::Push {
+            value: PushValue::Int16(0),
+        })
+        .label("end")
+        .bf("skip")
+        .push_int(1)
+        .pop_var("y")
+        .label("skip")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::If {
+                cond: Box::new(binary(ident("a"), BinaryOp::And, ident("b"))),
+                then: block(vec![assign("y", int(1))]),
+                r#else: None,
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn for_loop_declaring_local() -> Result<()> {
+    let out = Assembler::default()
+        .push_int(0)
+        .pop_local("i")
+        .label("head")
+        .push_local("i")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .call("foo", 0)
+        .increment(|v| v.push_local("i"), |v| v.pop_local("i"))
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::For {
+                initializer: Box::new(ast::Statement::Var(vec![("i".to_owned(), Some(int(0)))])),
+                condition: Box::new(binary(ident("i"), BinaryOp::LessThan, int(10))),
+                iterator: Box::new(increment("i")),
+                body: block(vec![call("foo")]),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn for_loop_counter_used_before() -> Result<()> {
+    let out = Assembler::default()
+        .push_local("i")
+        .pop_var("y")
+        .push_int(0)
+        .pop_local("i")
+        .label("head")
+        .push_local("i")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .call("foo", 0)
+        .increment(|v| v.push_local("i"), |v| v.pop_local("i"))
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Var(vec![("i".to_owned(), None)]),
+            assign("y", ident("i")),
+            ast::Statement::For {
+                initializer: Box::new(assign("i", int(0))),
+                condition: Box::new(binary(ident("i"), BinaryOp::LessThan, int(10))),
+                iterator: Box::new(increment("i")),
+                body: block(vec![call("foo")]),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn while_loop_not_testing_counter() -> Result<()> {
+    let out = Assembler::default()
+        .push_int(5)
+        .pop_var("x")
+        .label("head")
+        .push_var("a")
+        .push_var("b")
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .call("foo", 0)
+        .increment(|v| v.push_var("x"), |v| v.pop_var("x"))
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign("x", int(5)),
+            ast::Statement::While(ast::LoopStmt {
+                target: Box::new(binary(ident("a"), BinaryOp::LessThan, ident("b"))),
+                body: block(vec![call("foo"), increment("x")]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn switch_falling_into_default() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .push_int(1)
+        .compare(ComparisonType::Equal)
+        .bt("case_1")
+        .b("default")
+        .label("case_1")
+        .call("foo", 0)
+        .label("default")
+        .call("bar", 0)
+        .b("end")
+        .label("end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Switch {
+                target: Box::new(ident("x")),
+                cases: vec![
+                    ast::SwitchCase {
+                        compare: Some(int(1)),
+                        body: ast::Block(vec![call("foo")]),
+                    },
+                    ast::SwitchCase {
+                        compare: None,
+                        body: ast::Block(vec![call("bar"), ast::Statement::Break]),
+                    },
+                ],
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn switch_falling_out_of_default() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .push_int(1)
+        .compare(ComparisonType::Equal)
+        .bt("case_1")
+        .b("default")
+        .label("default")
+        .call("foo", 0)
+        .label("case_1")
+        .call("bar", 0)
+        .b("end")
+        .label("end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Switch {
+                target: Box::new(ident("x")),
+                cases: vec![
+                    ast::SwitchCase {
+                        compare: None,
+                        body: ast::Block(vec![call("foo")]),
+                    },
+                    ast::SwitchCase {
+                        compare: Some(int(1)),
+                        body: ast::Block(vec![call("bar"), ast::Statement::Break]),
+                    },
+                ],
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn function_declaration() -> Result<()> {
+    let out = Assembler::default()
+        .b("over")
+        .label("body")
+        .push_int(1)
+        .pop_var("y")
+        .exit()
+        .label("over")
+        .declare("gml_Script_foo", "body", 0, "foo")
+        .call("foo", 0)
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Function {
+                name: "foo".to_owned(),
+                is_constructor: false,
+                inherit: None,
+                params: Vec::new(),
+                body: ast::Block(vec![assign("y", int(1)), ast::Statement::Return(None)]),
+            },
+            call("foo"),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn function_declaration_with_parameters() -> Result<()> {
+    let out = Assembler::default()
+        .locals("gml_Script_foo", &["arguments", "a", "b"])
+        .b("over")
+        .label("body")
+        .push_var("argument1")
+        .push_var("undefined")
+        .compare(ComparisonType::Equal)
+        .bf("skip")
+        .push_int(3)
+        .pop_var("argument1")
+        .label("skip")
+        .push_var("argument0")
+        .push_var("argument1")
+        .emit(Instruction::Add {
+            augend: DataType::Variable,
+            addend: DataType::Variable,
+        })
+        .pop_var("y")
+        .exit()
+        .label("over")
+        .declare("gml_Script_foo", "body", 2, "foo")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Function {
+                name: "foo".to_owned(),
+                is_constructor: false,
+                inherit: None,
+                params: vec![
+                    ast::Param {
+                        name: "a".to_owned(),
+                        default: ast::Expr::Constant(Constant::Undefined),
+                    },
+                    ast::Param {
+                        name: "b".to_owned(),
+                        default: int(3),
+                    },
+                ],
+                body: ast::Block(vec![
+                    assign("y", binary(ident("a"), BinaryOp::Add, ident("b"))),
+                    ast::Statement::Return(None),
+                ]),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn nested_function_declarations() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .bf("end")
+        .b("over_foo")
+        .label("foo")
+        .b("over_bar")
+        .label("bar")
+        .push_int(1)
+        .pop_var("y")
+        .exit()
+        .label("over_bar")
+        .declare("gml_Script_bar", "bar", 0, "bar")
+        .exit()
+        .label("over_foo")
+        .declare("gml_Script_foo", "foo", 0, "foo")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    let function = |name: &str, body| ast::Statement::Function {
+        name: name.to_owned(),
+        is_constructor: false,
+        inherit: None,
+        params: Vec::new(),
+        body: ast::Block(body),
+    };
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::If {
+                cond: Box::new(ident("x")),
+                then: block(vec![function(
+                    "foo",
+                    vec![
+                        function(
+                            "bar",
+                            vec![assign("y", int(1)), ast::Statement::Return(None)]
+                        ),
+                        ast::Statement::Return(None),
+                    ],
+                )]),
+                r#else: None,
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn struct_literal() -> Result<()> {
+    let out = Assembler::default()
+        .b("over")
+        .label("body")
+        .push_var("argument0")
+        .pop_var("a")
+        .exit()
+        .label("over")
+        .declare_constructor("gml_Script____struct___0", "body", 1, "___struct___0")
+        .push_var("x")
+        .push_int(1)
+        .emit(Instruction::Add {
+            augend: DataType::Int32,
+            addend: DataType::Variable,
+        })
+        .push_function("gml_Script____struct___0")
+        .call_value("@@NewGMLObject@@", 2)
+        .pop_var("s")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign(
+                "s",
+                ast::Expr::Object(vec![ast::Field::Value(
+                    "a".to_owned(),
+                    binary(ident("x"), BinaryOp::Add, int(1)),
+                )]),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn function_rebound_to_other() -> Result<()> {
+    let out = Assembler::default()
+        .b("over")
+        .label("body")
+        .push_int(1)
+        .pop_var("y")
+        .exit()
+        .label("over")
+        .declare("gml_Script_foo", "body", 0, "foo")
+        .push_function("gml_Script_foo")
+        .call_value("@@Other@@", 0)
+        .call_value("method", 2)
+        .pop_var("cb")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Function {
+                name: "foo".to_owned(),
+                is_constructor: false,
+                inherit: None,
+                params: Vec::new(),
+                body: ast::Block(vec![assign("y", int(1)), ast::Statement::Return(None)]),
+            },
+            assign(
+                "cb",
+                ast::Expr::Call(ast::Call {
+                    base: Box::new(ident("method")),
+                    arguments: vec![ast::Expr::Other, ident("gml_Script_foo")],
+                    has_new: false,
+                }),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn function_declarations_with_shared_prefix() -> Result<()> {
+    let out = Assembler::default()
+        .b("over_foo_bar")
+        .label("foo_bar")
+        .push_int(1)
+        .pop_var("y")
+        .exit()
+        .label("over_foo_bar")
+        .declare("gml_Script_foo_bar", "foo_bar", 0, "foo_bar")
+        .b("over_foo")
+        .label("foo")
+        .push_int(2)
+        .pop_var("y")
+        .exit()
+        .label("over_foo")
+        .declare("gml_Script_foo", "foo", 0, "foo")
+        .exit()
+        .decompile()?;
+
+    let function = |name: &str, value| ast::Statement::Function {
+        name: name.to_owned(),
+        is_constructor: false,
+        inherit: None,
+        params: Vec::new(),
+        body: ast::Block(vec![assign("y", int(value)), ast::Statement::Return(None)]),
+    };
+    assert_eq!(
+        out.0,
+        [
+            function("foo_bar", 1),
+            function("foo", 2),
+            ast::Statement::Return(None)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn new_expression() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("b")
+        .push_var("a")
+        .push_function("gml_Script_Foo")
+        .call_value("@@NewGMLObject@@", 3)
+        .pop_var("s")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign(
+                "s",
+                ast::Expr::Call(ast::Call {
+                    base: Box::new(ident("Foo")),
+                    arguments: vec![ident("a"), ident("b")],
+                    has_new: true,
+                }),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn array_literal() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("c")
+        .push_var("b")
+        .push_var("a")
+        .call_value("@@NewGMLArray@@", 3)
+        .pop_var("s")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign(
+                "s",
+                ast::Expr::Array(vec![ident("a"), ident("b"), ident("c")]),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn constructor_inheriting() -> Result<()> {
+    let out = Assembler::default()
+        .b("over")
+        .label("body")
+        .push_var("argument0")
+        .call("gml_Script_Parent", 1)
+        .push_function("gml_Script_Parent")
+        .call("@@CopyStatic@@", 1)
+        .exit()
+        .label("over")
+        .declare_constructor("gml_Script_Child", "body", 1, "Child")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Function {
+                name: "Child".to_owned(),
+                is_constructor: true,
+                inherit: Some(ast::Call {
+                    base: Box::new(ident("Parent")),
+                    arguments: vec![ident("argument0")],
+                    has_new: false,
+                }),
+                params: vec![ast::Param {
+                    name: "argument0".to_owned(),
+                    default: ast::Expr::Constant(Constant::Undefined),
+                }],
+                body: ast::Block(vec![ast::Statement::Return(None)]),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn call_arguments_in_source_order() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("b")
+        .push_var("a")
+        .call("foo", 2)
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Call(ast::Call {
+                base: Box::new(ident("foo")),
+                arguments: vec![ident("a"), ident("b")],
+                has_new: false,
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn call_argument_underflow() {
+    let error = Assembler::default()
+        .push_var("a")
+        .call("foo", 2)
+        .exit()
+        .decompile()
+        .expect_err("a call with too few arguments on the stack can't be decompiled");
+    assert!(error.to_string().contains("stuck"), "{error}");
+}
+
+#[test]
+fn instance_scoped_variables() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let player = assembler.object("obj_player");
+    let out = assembler
+        .push_int(player)
+        .push_field("hp")
+        .pop_var("a")
+        .push_global("score")
+        .pop_var("b")
+        .push_int(-2)
+        .push_field("w")
+        .pop_var("c")
+        .exit()
+        .decompile()?;
+
+    let field = |base, field: &str| ast::Expr::Field {
+        base: Box::new(base),
+        field: field.to_owned(),
+    };
+    assert_eq!(
+        out.0,
+        [
+            assign("a", field(ident("obj_player"), "hp")),
+            assign("b", field(ast::Expr::Global, "score")),
+            assign("c", field(ast::Expr::Other, "w")),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+fn index(name: &str, indexes: Vec<ast::Expr>) -> ast::MutableExpr {
+    ast::MutableExpr::Index {
+        base: Box::new(ident(name)),
+        accessor_type: None,
+        indexes,
+    }
+}
+
+#[test]
+fn array_compound_assignment() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let array = assembler.variable_of("a", VariableType::Array, InstanceType::Self_(None));
+    let out = assembler
+        .push_int(-1)
+        .push_var("i")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Int32,
+            size: 1,
+        })
+        .push_array("a")
+        .push_int(1)
+        .emit(Instruction::Add {
+            augend: DataType::Int32,
+            addend: DataType::Variable,
+        })
+        .emit(Instruction::Pop {
+            variable: array,
+            type1: DataType::Int32,
+            type2: DataType::Variable,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Assignment {
+                target: index("a", vec![ident("i")]),
+                op: ast::AssignmentOp::PlusEqual,
+                value: Box::new(int(1)),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn array_assignment_swapping_value() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let array = assembler.variable_of("a", VariableType::Array, InstanceType::Self_(None));
+    let out = assembler
+        .push_int(-1)
+        .push_var("i")
+        .push_var("x")
+        // moves `x` below the instance and index it was pushed after
+        .emit(Instruction::DuplicateSwap {
+            data_type: DataType::Int32,
+            size1: 1,
+            size2: 2,
+        })
+        .emit(Instruction::Pop {
+            variable: array,
+            type1: DataType::Variable,
+            type2: DataType::Variable,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Assignment {
+                target: index("a", vec![ident("i")]),
+                op: ast::AssignmentOp::Equal,
+                value: Box::new(ident("x")),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn try_catch() -> Result<()> {
+    let out = Assembler::default()
+        .try_hook(|v| v.push_int(-1), |v| v.push_address("catch"))
+        .call("foo", 0)
+        .call("@@try_unhook@@", 0)
+        .b("end")
+        .label("catch")
+        .pop_local("err")
+        .call("@@try_unhook@@", 0)
+        .call("bar", 0)
+        .call("@@finish_catch@@", 0)
+        .b("end")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::TryCatch {
+                try_block: block(vec![call("foo")]),
+                catch: Some(("err".to_owned(), block(vec![call("bar")]))),
+                finally_block: None,
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn try_finally() -> Result<()> {
+    let out = Assembler::default()
+        .try_hook(|v| v.push_address("finally"), |v| v.push_int(-1))
+        .call("foo", 0)
+        .call("@@try_unhook@@", 0)
+        .b("finally")
+        .label("finally")
+        .call("bar", 0)
+        .call("@@finish_finally@@", 0)
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::TryCatch {
+                try_block: block(vec![call("foo")]),
+                catch: None,
+                finally_block: Some(block(vec![call("bar")])),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn try_catch_finally() -> Result<()> {
+    let out = Assembler::default()
+        .try_hook(|v| v.push_address("finally"), |v| v.push_address("catch"))
+        .call("foo", 0)
+        .call("@@try_unhook@@", 0)
+        .b("finally")
+        .label("catch")
+        .pop_local("err")
+        .call("@@try_unhook@@", 0)
+        .call("bar", 0)
+        .call("@@finish_catch@@", 0)
+        .b("finally")
+        .label("finally")
+        .call("baz", 0)
+        .call("@@finish_finally@@", 0)
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::TryCatch {
+                try_block: block(vec![call("foo")]),
+                catch: Some(("err".to_owned(), block(vec![call("bar")]))),
+                finally_block: Some(block(vec![call("baz")])),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn with() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let player = assembler.object("obj_player");
+    let out = assembler
+        .push_int(player)
+        .pushenv("popenv")
+        .label("body")
+        .call("foo", 0)
+        .label("popenv")
+        .popenv("body")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::With(ast::LoopStmt {
+                target: Box::new(ident("obj_player")),
+                body: block(vec![call("foo")]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn with_break() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let player = assembler.object("obj_player");
+    let out = assembler
+        .push_int(player)
+        .pushenv("popenv")
+        .label("body")
+        .call("foo", 0)
+        .b("break")
+        .label("popenv")
+        .popenv("body")
+        .b("end")
+        .label("break")
+        .emit(Instruction::PopWithContextExit)
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::With(ast::LoopStmt {
+                target: Box::new(ident("obj_player")),
+                body: block(vec![call("foo"), ast::Statement::Break]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn while_loop_break() -> Result<()> {
+    let out = Assembler::default()
+        .label("head")
+        .push_var("x")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .push_var("a")
+        .bf("skip")
+        .b("end")
+        .label("skip")
+        .call("foo", 0)
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::While(ast::LoopStmt {
+                target: Box::new(binary(ident("x"), BinaryOp::LessThan, int(10))),
+                body: block(vec![
+                    ast::Statement::If {
+                        cond: Box::new(ident("a")),
+                        then: block(vec![ast::Statement::Break]),
+                        r#else: None,
+                    },
+                    call("foo"),
+                ]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn while_loop_continue() -> Result<()> {
+    let out = Assembler::default()
+        .label("head")
+        .push_var("x")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .push_var("a")
+        .bf("skip")
+        .b("head")
+        .label("skip")
+        .call("foo", 0)
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::While(ast::LoopStmt {
+                target: Box::new(binary(ident("x"), BinaryOp::LessThan, int(10))),
+                body: block(vec![
+                    ast::Statement::If {
+                        cond: Box::new(ident("a")),
+                        then: block(vec![ast::Statement::Continue]),
+                        r#else: None,
+                    },
+                    call("foo"),
+                ]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn for_loop_continue() -> Result<()> {
+    let out = Assembler::default()
+        .push_int(0)
+        .pop_local("i")
+        .label("head")
+        .push_local("i")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .push_var("a")
+        .bf("skip")
+        .b("step")
+        .label("skip")
+        .call("foo", 0)
+        .label("step")
+        .increment(|v| v.push_local("i"), |v| v.pop_local("i"))
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::For {
+                initializer: Box::new(ast::Statement::Var(vec![("i".to_owned(), Some(int(0)))])),
+                condition: Box::new(binary(ident("i"), BinaryOp::LessThan, int(10))),
+                iterator: Box::new(increment("i")),
+                body: block(vec![
+                    ast::Statement::If {
+                        cond: Box::new(ident("a")),
+                        then: block(vec![ast::Statement::Continue]),
+                        r#else: None,
+                    },
+                    call("foo"),
+                ]),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn continue_in_switch() -> Result<()> {
+    let out = Assembler::default()
+        .label("head")
+        .push_var("x")
+        .push_int(10)
+        .compare(ComparisonType::LessThan)
+        .bf("end")
+        .push_var("y")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .push_int(1)
+        .compare(ComparisonType::Equal)
+        .bt("case_1")
+        .b("switch_end")
+        .label("case_1")
+        // the switched value is dropped before leaving the switch
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .b("head")
+        .label("switch_end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .call("foo", 0)
+        .b("head")
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::While(ast::LoopStmt {
+                target: Box::new(binary(ident("x"), BinaryOp::LessThan, int(10))),
+                body: block(vec![
+                    ast::Statement::Switch {
+                        target: Box::new(ident("y")),
+                        cases: vec![ast::SwitchCase {
+                            compare: Some(int(1)),
+                            body: ast::Block(vec![ast::Statement::Continue]),
+                        }],
+                    },
+                    call("foo"),
+                ]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn do_until() -> Result<()> {
+    let out = Assembler::default()
+        .label("body")
+        .call("foo", 0)
+        .push_var("x")
+        .push_int(5)
+        .compare(ComparisonType::GreaterThan)
+        // loops back while the condition is false, until it's true
+        .bf("body")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::DoUntil(ast::LoopStmt {
+                target: Box::new(binary(ident("x"), BinaryOp::GreaterThan, int(5))),
+                body: block(vec![call("foo")]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn repeat() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("n")
+        .repeat_head("end")
+        .label("body")
+        .call("foo", 0)
+        .repeat_tail("body")
+        .label("end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Int32,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Repeat(ast::LoopStmt {
+                target: Box::new(ident("n")),
+                body: block(vec![call("foo")]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn repeat_with_control_flow() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("n")
+        .repeat_head("end")
+        .label("body")
+        .push_var("a")
+        .bf("tail")
+        .call("foo", 0)
+        .label("tail")
+        .call("bar", 0)
+        .repeat_tail("body")
+        .label("end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Int32,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Repeat(ast::LoopStmt {
+                target: Box::new(ident("n")),
+                body: block(vec![
+                    ast::Statement::If {
+                        cond: Box::new(ident("a")),
+                        then: block(vec![call("foo")]),
+                        r#else: None,
+                    },
+                    call("bar"),
+                ]),
+            }),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn ternary() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("a")
+        .bf("false")
+        .push_var("b")
+        .b("end")
+        .label("false")
+        .push_var("c")
+        .label("end")
+        .pop_var("y")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign(
+                "y",
+                ast::Expr::Ternary {
+                    cond: Box::new(ident("a")),
+                    if_true: Box::new(ident("b")),
+                    if_false: Box::new(ident("c")),
+                },
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn null_coalesce() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("a")
+        .emit(Instruction::IsNullishValue)
+        .bf("end")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .push_var("b")
+        .label("end")
+        .pop_var("y")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            assign("y", binary(ident("a"), BinaryOp::NullCoalesce, ident("b"))),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn null_coalesce_assignment() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("a")
+        .emit(Instruction::IsNullishValue)
+        .bf("skip")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .push_var("b")
+        .pop_var("a")
+        .b("end")
+        .label("skip")
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .label("end")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Assignment {
+                target: ast::MutableExpr::Ident("a".to_owned()),
+                op: ast::AssignmentOp::NullCoalesce,
+                value: Box::new(ident("b")),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn accessor_getters() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("i")
+        .push_var("l")
+        .call_value("ds_list_find_value", 2)
+        .pop_var("a")
+        .push_var("j")
+        .push_var("i")
+        .push_var("g")
+        .call_value("ds_grid_get", 3)
+        .pop_var("b")
+        .exit()
+        .decompile()?;
+
+    let read = |base: &str, accessor_type, indexes| ast::Expr::Index {
+        base: Box::new(ident(base)),
+        accessor_type: Some(accessor_type),
+        indexes,
+    };
+    assert_eq!(
+        out.0,
+        [
+            assign("a", read("l", ast::AccessorType::List, vec![ident("i")])),
+            assign(
+                "b",
+                read("g", ast::AccessorType::Grid, vec![ident("i"), ident("j")]),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn accessor_setters() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("v")
+        .push_var("k")
+        .push_var("m")
+        .call("ds_map_set", 3)
+        .push_var("v")
+        .push_var("j")
+        .push_var("i")
+        .push_var("g")
+        .call("ds_grid_set", 4)
+        .exit()
+        .decompile()?;
+
+    let write = |base: &str, accessor_type, indexes| ast::Statement::Assignment {
+        target: ast::MutableExpr::Index {
+            base: Box::new(ident(base)),
+            accessor_type: Some(accessor_type),
+            indexes,
+        },
+        op: ast::AssignmentOp::Equal,
+        value: Box::new(ident("v")),
+    };
+    assert_eq!(
+        out.0,
+        [
+            write("m", ast::AccessorType::Map, vec![ident("k")]),
+            write("g", ast::AccessorType::Grid, vec![ident("i"), ident("j")]),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn array_reads() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let multipush =
+        assembler.variable_of("c", VariableType::ArrayPushAF, InstanceType::Self_(None));
+    let out = assembler
+        .push_int(-1)
+        .push_var("i")
+        .push_array("a")
+        .pop_var("x")
+        // `b[i, j]` is flattened to `b[i * 32000 + j]` before 2.3
+        .push_int(-1)
+        .push_var("i")
+        .push_int(32000)
+        .emit(Instruction::Multiply {
+            multiplicand: DataType::Int32,
+            multiplier: DataType::Variable,
+        })
+        .push_var("j")
+        .emit(Instruction::Add {
+            augend: DataType::Variable,
+            addend: DataType::Variable,
+        })
+        .push_array("b")
+        .pop_var("y")
+        .push_int(-1)
+        .push_var("i")
+        .emit(Instruction::Push {
+            value: PushValue::Variable(multipush),
+        })
+        .push_var("j")
+        .emit(Instruction::PushArrayFinal)
+        .pop_var("z")
+        .exit()
+        .decompile()?;
+
+    let element = |base, indexes| ast::Expr::Index {
+        base: Box::new(base),
+        accessor_type: None,
+        indexes,
+    };
+    assert_eq!(
+        out.0,
+        [
+            assign("x", element(ident("a"), vec![ident("i")])),
+            assign("y", element(ident("b"), vec![ident("i"), ident("j")])),
+            assign(
+                "z",
+                element(element(ident("c"), vec![ident("i")]), vec![ident("j")]),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn chained_array_assignment() -> Result<()> {
+    let mut assembler = Assembler::default();
+    let multipushpop =
+        assembler.variable_of("a", VariableType::ArrayPopAF, InstanceType::Self_(None));
+    let out = assembler
+        .push_var("v")
+        .push_int(-1)
+        .push_var("i")
+        .emit(Instruction::Push {
+            value: PushValue::Variable(multipushpop),
+        })
+        .push_var("j")
+        .emit(Instruction::PopArrayFinal)
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Assignment {
+                target: ast::MutableExpr::Index {
+                    base: Box::new(ast::Expr::Index {
+                        base: Box::new(ident("a")),
+                        accessor_type: None,
+                        indexes: vec![ident("i")],
+                    }),
+                    accessor_type: None,
+                    indexes: vec![ident("j")],
+                },
+                op: ast::AssignmentOp::Equal,
+                value: Box::new(ident("v")),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn call_variable() -> Result<()> {
+    let out = Assembler::default()
+        .push_int(2)
+        .push_int(1)
+        .call_value("@@This@@", 0)
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .push_field("foo")
+        .emit(Instruction::CallVariable { argument_count: 2 })
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .push_int(-1)
+        .push_var("fn_var")
+        .emit(Instruction::CallVariable { argument_count: 0 })
+        .emit(Instruction::PopDiscard {
+            data_type: DataType::Variable,
+        })
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Call(ast::Call {
+                base: Box::new(ast::Expr::Field {
+                    base: Box::new(ast::Expr::This),
+                    field: "foo".to_owned(),
+                }),
+                arguments: vec![int(1), int(2)],
+                has_new: false,
+            }),
+            call("fn_var"),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn compound_assignments() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .push_int(5)
+        .emit(Instruction::Subtract {
+            minuend: DataType::Int32,
+            subtrahend: DataType::Variable,
+        })
+        .pop_var("x")
+        .push_var("y")
+        .push_var("z")
+        .emit(Instruction::Multiply {
+            multiplicand: DataType::Variable,
+            multiplier: DataType::Variable,
+        })
+        .pop_var("y")
+        // the assigned variable has to come first
+        .push_var("z")
+        .push_var("x")
+        .emit(Instruction::Subtract {
+            minuend: DataType::Variable,
+            subtrahend: DataType::Variable,
+        })
+        .pop_var("x")
+        .exit()
+        .decompile()?;
+
+    let compound = |name: &str, op, value| ast::Statement::Assignment {
+        target: ast::MutableExpr::Ident(name.to_owned()),
+        op,
+        value: Box::new(value),
+    };
+    assert_eq!(
+        out.0,
+        [
+            compound("x", ast::AssignmentOp::MinusEqual, int(5)),
+            compound("y", ast::AssignmentOp::MultEqual, ident("z")),
+            assign("x", binary(ident("z"), BinaryOp::Sub, ident("x"))),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn prefix_and_postfix_expressions() -> Result<()> {
+    let one = Instruction::Push {
+        value: PushValue::Int16(1),
+    };
+    let out = Assembler::default()
+        .push_var("x")
+        .emit(one.clone())
+        .emit(Instruction::Add {
+            augend: DataType::Int32,
+            addend: DataType::Variable,
+        })
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .pop_var("x")
+        .pop_var("y")
+        .push_var("x")
+        .emit(Instruction::Duplicate {
+            data_type: DataType::Variable,
+            size: 0,
+        })
+        .emit(one)
+        .emit(Instruction::Subtract {
+            minuend: DataType::Int32,
+            subtrahend: DataType::Variable,
+        })
+        .pop_var("x")
+        .pop_var("z")
+        .exit()
+        .decompile()?;
+
+    let mutation = |op| ast::Mutation {
+        op,
+        target: Box::new(ast::MutableExpr::Ident("x".to_owned())),
+    };
+    assert_eq!(
+        out.0,
+        [
+            assign("y", ast::Expr::Prefix(mutation(ast::MutationOp::Increment)),),
+            assign(
+                "z",
+                ast::Expr::Postfix(mutation(ast::MutationOp::Decrement)),
+            ),
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn add_one_is_not_increment() -> Result<()> {
+    let out = Assembler::default()
+        .push_var("x")
+        .push_int(1)
+        .emit(Instruction::Add {
+            augend: DataType::Int32,
+            addend: DataType::Variable,
+        })
+        .pop_var("x")
+        .exit()
+        .decompile()?;
+
+    assert_eq!(
+        out.0,
+        [
+            ast::Statement::Assignment {
+                target: ast::MutableExpr::Ident("x".to_owned()),
+                op: ast::AssignmentOp::PlusEqual,
+                value: Box::new(int(1)),
+            },
+            ast::Statement::Return(None),
+        ]
+    );
+    Ok(())
+}